@@ -0,0 +1,275 @@
+use std::{collections::HashMap, f64::consts::PI, fmt, str::FromStr, sync::Mutex};
+
+use rand::{distributions::Uniform, Rng};
+use serde::Serialize;
+
+/// How many bones each payee of a sustained payment receives.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Amount {
+    Fixed(u64),
+    /// Uniform between `min` and `max`, inclusive.
+    Uniform {
+        min: u64,
+        max: u64,
+    },
+    /// Log-normal around `median`, with `sigma` the standard deviation
+    /// of the underlying normal distribution.
+    LogNormal {
+        median: u64,
+        sigma: f64,
+    },
+    /// A percentage of the payer's known balance, split between the
+    /// payees.
+    Percent(f64),
+}
+
+impl Default for Amount {
+    fn default() -> Self {
+        Amount::Fixed(1)
+    }
+}
+
+impl Amount {
+    /// Draws the bones for each of `payees` payees of a payer known
+    /// to hold `balance`. The result never adds up to more than the
+    /// balance, and is 0 when the balance cannot cover a bone each.
+    pub fn sample<R: Rng>(&self, rng: &mut R, balance: u64, payees: usize) -> u64 {
+        let payees = payees.max(1) as u64;
+        let bones = match self {
+            Amount::Fixed(bones) => *bones,
+            Amount::Uniform { min, max } => rng.sample(Uniform::new_inclusive(*min, *max)),
+            Amount::LogNormal { median, sigma } => {
+                // Box-Muller, with u1 in (0, 1] so its log is finite
+                let u1: f64 = 1.0 - rng.gen::<f64>();
+                let u2: f64 = rng.gen();
+                let z = (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos();
+                (*median as f64 * (sigma * z).exp()).round() as u64
+            }
+            Amount::Percent(percent) => (balance as f64 * percent / 100.0) as u64 / payees,
+        };
+        bones.min(balance / payees)
+    }
+}
+
+/// Parses `fixed:BONES`, `uniform:MIN:MAX`, `lognormal:MEDIAN:SIGMA`
+/// or `percent:P`.
+impl FromStr for Amount {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let invalid = || format!("invalid amount {}", s);
+        let bones = |i: usize| -> Result<u64, String> {
+            parts
+                .get(i)
+                .and_then(|p| p.parse().ok())
+                .ok_or_else(invalid)
+        };
+        let float = |i: usize| -> Result<f64, String> {
+            parts
+                .get(i)
+                .and_then(|p| p.parse::<f64>().ok())
+                .filter(|f| f.is_finite() && *f >= 0.0)
+                .ok_or_else(invalid)
+        };
+
+        let (amount, arity) = match parts[0] {
+            "fixed" => (Amount::Fixed(bones(1)?), 2),
+            "uniform" => {
+                let (min, max) = (bones(1)?, bones(2)?);
+                if min > max {
+                    return Err(format!("uniform minimum above maximum in {}", s));
+                }
+                (Amount::Uniform { min, max }, 3)
+            }
+            "lognormal" => (
+                Amount::LogNormal {
+                    median: bones(1)?,
+                    sigma: float(2)?,
+                },
+                3,
+            ),
+            "percent" => match float(1)? {
+                percent if percent <= 100.0 => (Amount::Percent(percent), 2),
+                _ => return Err(format!("percent above 100 in {}", s)),
+            },
+            _ => return Err(format!(
+                "{}, expected fixed:BONES, uniform:MIN:MAX, lognormal:MEDIAN:SIGMA or percent:P",
+                invalid()
+            )),
+        };
+        if parts.len() != arity {
+            return Err(invalid());
+        }
+        Ok(amount)
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Amount::Fixed(bones) => write!(f, "fixed:{}", bones),
+            Amount::Uniform { min, max } => write!(f, "uniform:{}:{}", min, max),
+            Amount::LogNormal { median, sigma } => write!(f, "lognormal:{}:{}", median, sigma),
+            Amount::Percent(percent) => write!(f, "percent:{}", percent),
+        }
+    }
+}
+
+/// Payer, payees and fee of a submitted txn.
+type InFlight = (String, Vec<(String, u64)>, u64);
+
+/// What each wallet is known to be able to spend during a run: its
+/// balance when the run started, less what it has sent, plus what it
+/// has received in txns that cleared. Payments are only sent once
+/// reserved against it, so no wallet is overdrawn mid-run.
+#[derive(Debug, Default)]
+pub struct Budget {
+    available: Mutex<HashMap<String, u64>>,
+    /// Submitted txns, by hash.
+    in_flight: Mutex<HashMap<String, InFlight>>,
+}
+
+impl Budget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces what `address` is known to hold.
+    pub fn set(&self, address: &str, balance: u64) {
+        self.available
+            .lock()
+            .unwrap()
+            .insert(address.to_string(), balance);
+    }
+
+    pub fn available(&self, address: &str) -> Option<u64> {
+        self.available.lock().unwrap().get(address).copied()
+    }
+
+    /// Takes `bones` from what `address` can spend, unless it holds
+    /// less than that or is not known at all.
+    pub fn reserve(&self, address: &str, bones: u64) -> bool {
+        match self.available.lock().unwrap().get_mut(address) {
+            Some(available) if *available >= bones => {
+                *available -= bones;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Gives back bones reserved for a payment that was not sent.
+    pub fn refund(&self, address: &str, bones: u64) {
+        if let Some(available) = self.available.lock().unwrap().get_mut(address) {
+            *available += bones;
+        }
+    }
+
+    /// Remembers a submitted payment, and the `fee` in bones reserved
+    /// along with it, until it clears or fails.
+    pub fn submitted(&self, hash: &str, payer: &str, payees: Vec<(String, u64)>, fee: u64) {
+        self.in_flight
+            .lock()
+            .unwrap()
+            .insert(hash.to_string(), (payer.to_string(), payees, fee));
+    }
+
+    /// Credits the payees of a cleared txn, or refunds the payer of a
+    /// failed one, fee and all. Txns not submitted through the budget
+    /// are ignored.
+    pub fn resolved(&self, hash: &str, cleared: bool) {
+        let (payer, payees, fee) = match self.in_flight.lock().unwrap().remove(hash) {
+            Some(txn) => txn,
+            None => return,
+        };
+        if cleared {
+            for (payee, bones) in payees {
+                self.refund(&payee, bones);
+            }
+        } else {
+            let total: u64 = payees.iter().map(|(_, bones)| bones).sum();
+            self.refund(&payer, total + fee);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Ok(Amount::Fixed(5)), "fixed:5".parse());
+        assert_eq!(
+            Ok(Amount::Uniform { min: 1, max: 9 }),
+            "uniform:1:9".parse()
+        );
+        assert_eq!(
+            Ok(Amount::LogNormal {
+                median: 100,
+                sigma: 0.5
+            }),
+            "lognormal:100:0.5".parse()
+        );
+        assert_eq!(Ok(Amount::Percent(2.5)), "percent:2.5".parse());
+        assert!("uniform:9:1".parse::<Amount>().is_err());
+        assert!("percent:101".parse::<Amount>().is_err());
+        assert!("fixed".parse::<Amount>().is_err());
+        assert!("fixed:1:2".parse::<Amount>().is_err());
+        assert!("lognormal:100:-1".parse::<Amount>().is_err());
+    }
+
+    #[test]
+    fn test_sample_is_reproducible_and_bounded() {
+        let amounts: Vec<Amount> = ["uniform:10:20", "lognormal:1000:1", "percent:10"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let draw = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            amounts
+                .iter()
+                .flat_map(|a| {
+                    (0..50)
+                        .map(|_| a.sample(&mut rng, 5_000, 2))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<u64>>()
+        };
+        assert_eq!(draw(7), draw(7));
+
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..100 {
+            let bones = amounts[0].sample(&mut rng, 5_000, 1);
+            assert!((10..=20).contains(&bones));
+            assert!(amounts[1].sample(&mut rng, 5_000, 2) <= 2_500);
+        }
+        assert_eq!(250, amounts[2].sample(&mut rng, 5_000, 2));
+        assert_eq!(0, Amount::Fixed(1).sample(&mut rng, 1, 2));
+    }
+
+    #[test]
+    fn test_budget() {
+        let budget = Budget::new();
+        budget.set("a", 10);
+        budget.set("b", 0);
+        assert!(!budget.reserve("c", 1));
+        assert!(!budget.reserve("a", 11));
+        assert!(budget.reserve("a", 7));
+
+        // The fee is spent once the txn clears
+        budget.submitted("cleared", "a", vec![("b".to_string(), 6)], 1);
+        budget.resolved("cleared", true);
+        assert_eq!(Some(3), budget.available("a"));
+        assert_eq!(Some(6), budget.available("b"));
+
+        // And given back with the rest when it fails
+        assert!(budget.reserve("b", 6));
+        budget.submitted("failed", "b", vec![("a".to_string(), 5)], 1);
+        budget.resolved("failed", false);
+        assert_eq!(Some(6), budget.available("b"));
+        assert_eq!(Some(3), budget.available("a"));
+    }
+}
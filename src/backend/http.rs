@@ -0,0 +1,279 @@
+use std::{
+    fmt,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures::future::join_all;
+use prost::Message;
+use serde_json::{json, Value};
+use tokio::{runtime::Runtime, sync::Semaphore};
+
+use super::{Account, BankError, ChainBackend, Result, TxnStatus};
+use crate::fee::FeeVars;
+use crate::txn::PaymentTxn;
+
+/// How long fetched fee variables are used before they are refetched.
+const FEE_VARS_TTL: Duration = Duration::from_secs(300);
+
+/// Talks to a Helium API server over HTTP. Requests run on a shared
+/// async runtime through one pooled client, so the number in flight
+/// is bounded by `concurrency` rather than by the calling threads.
+pub struct HttpBackend {
+    api: Api,
+    runtime: Runtime,
+    fee_vars: Mutex<Option<(Instant, FeeVars)>>,
+}
+
+impl HttpBackend {
+    /// `concurrency` caps the requests in flight at once, across every
+    /// thread using the backend.
+    pub fn new(api_url: &str, concurrency: usize) -> Result<Self> {
+        let concurrency = concurrency.max(1);
+        let client = reqwest::Client::builder()
+            .pool_max_idle_per_host(concurrency)
+            .build()
+            .map_err(BankError::api)?;
+        let runtime = tokio::runtime::Builder::new()
+            .threaded_scheduler()
+            .enable_all()
+            .build()
+            .map_err(|e| BankError::Config(format!("Failed to start network runtime: {}", e)))?;
+        Ok(Self {
+            api: Api {
+                base_url: api_url.trim_end_matches('/').to_string(),
+                client,
+                limit: Arc::new(Semaphore::new(concurrency)),
+            },
+            runtime,
+            fee_vars: Mutex::new(None),
+        })
+    }
+
+    /// The chain's fee variables, fetched at most once every
+    /// `FEE_VARS_TTL`.
+    fn fee_vars(&self) -> Result<FeeVars> {
+        let mut cached = self.fee_vars.lock().unwrap();
+        match *cached {
+            Some((fetched, vars)) if fetched.elapsed() < FEE_VARS_TTL => Ok(vars),
+            _ => {
+                let vars = self.block_on(self.api.clone().fee_vars())?;
+                *cached = Some((Instant::now(), vars));
+                Ok(vars)
+            }
+        }
+    }
+
+    /// Runs `fut` on the runtime, blocking the calling thread until
+    /// it is done.
+    fn block_on<T: Send + 'static>(&self, fut: impl Future<Output = T> + Send + 'static) -> T {
+        futures::executor::block_on(self.runtime.spawn(fut)).expect("network task panicked")
+    }
+
+    /// Runs `f` for every item concurrently, returning the results in
+    /// the order of `items`.
+    fn map_all<I, F, Fut, T>(&self, items: &[I], f: F) -> Vec<T>
+    where
+        I: Clone,
+        F: Fn(Api, I) -> Fut,
+        Fut: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let futs: Vec<Fut> = items
+            .iter()
+            .map(|item| f(self.api.clone(), item.clone()))
+            .collect();
+        self.block_on(join_all(futs))
+    }
+}
+
+/// What a request needs, cheap to clone into each task.
+#[derive(Clone)]
+struct Api {
+    base_url: String,
+    client: reqwest::Client,
+    limit: Arc<Semaphore>,
+}
+
+impl Api {
+    async fn get_json(&self, path: &str) -> Result<Value> {
+        let _permit = self.limit.acquire().await;
+        let url = format!("{}/v1/{}", self.base_url, path);
+        let response = self.client.get(&url).send().await.map_err(BankError::api)?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(Value::Null);
+        }
+        response
+            .error_for_status()
+            .map_err(BankError::api)?
+            .json()
+            .await
+            .map_err(BankError::api)
+    }
+
+    async fn post_json(&self, path: &str, body: &Value) -> Result<Value> {
+        let _permit = self.limit.acquire().await;
+        let url = format!("{}/v1/{}", self.base_url, path);
+        let response = self
+            .client
+            .post(&url)
+            .json(body)
+            .send()
+            .await
+            .map_err(BankError::api)?;
+        response
+            .error_for_status()
+            .map_err(BankError::api)?
+            .json()
+            .await
+            .map_err(BankError::api)
+    }
+
+    async fn account(self, address: String) -> Result<Account> {
+        let reply = self.get_json(&format!("accounts/{}", address)).await?;
+        let data = &reply["data"];
+        Ok(Account {
+            address: data["address"].as_str().unwrap_or(&address).to_string(),
+            balance: field(data, "balance")?,
+            nonce: field(data, "nonce")?,
+            speculative_nonce: field(data, "speculative_nonce")?,
+        })
+    }
+
+    async fn fee_vars(self) -> Result<FeeVars> {
+        let vars = self.get_json("vars").await?;
+        let price = self.get_json("oracle/prices/current").await?;
+        let vars = &vars["data"];
+        Ok(FeeVars {
+            // Chains that never switched fees on do not set it
+            txn_fee_multiplier: vars["txn_fee_multiplier"].as_u64().unwrap_or(0),
+            dc_payload_size: field(vars, "dc_payload_size")?,
+            oracle_price: field(&price["data"], "price")?,
+        })
+    }
+
+    async fn max_payments(self) -> Result<Option<usize>> {
+        let vars = self.get_json("vars").await?;
+        Ok(vars["data"]["max_payments"].as_u64().map(|n| n as usize))
+    }
+
+    async fn height(self) -> Result<u64> {
+        let reply = self.get_json("blocks/height").await?;
+        field(&reply["data"], "height")
+    }
+
+    /// Submits an encoded txn, returning the hash and how long the
+    /// API took to answer.
+    async fn submit(self, encoded: Result<String>) -> (Result<String>, Duration) {
+        let started = Instant::now();
+        let r = match encoded {
+            Ok(encoded) => self
+                .post_json("pending_transactions", &json!({ "txn": encoded }))
+                .await
+                .and_then(|reply| {
+                    reply["data"]["hash"]
+                        .as_str()
+                        .map(str::to_string)
+                        .ok_or_else(|| BankError::Api(format!("unexpected reply: {}", reply)))
+                }),
+            Err(e) => Err(e),
+        };
+        (r, started.elapsed())
+    }
+
+    async fn txn_status(self, hash: String) -> Result<TxnStatus> {
+        // Cleared txns show up in the transactions endpoint with a height
+        let cleared = self.get_json(&format!("transactions/{}", hash)).await?;
+        if let Some(height) = cleared["data"]["height"].as_u64() {
+            return Ok(TxnStatus::Cleared(Some(height)));
+        }
+
+        let pending = self
+            .get_json(&format!("pending_transactions/{}", hash))
+            .await?;
+        let entry = match pending["data"].as_array().and_then(|d| d.first()) {
+            Some(entry) => entry,
+            None => return Ok(TxnStatus::Unknown),
+        };
+        Ok(match entry["status"].as_str() {
+            Some("pending") => TxnStatus::Pending,
+            Some("cleared") => TxnStatus::Cleared(None),
+            Some("failed") => TxnStatus::Failed(
+                entry["failed_reason"]
+                    .as_str()
+                    .unwrap_or("unknown")
+                    .to_string(),
+            ),
+            _ => TxnStatus::Unknown,
+        })
+    }
+}
+
+fn field(data: &Value, name: &str) -> Result<u64> {
+    data[name]
+        .as_u64()
+        .ok_or_else(|| BankError::Api(format!("reply is missing {}: {}", name, data)))
+}
+
+/// The base64 protobuf envelope the API expects for `txn`.
+fn encode(txn: &PaymentTxn) -> Result<String> {
+    let envelope = txn.in_envelope()?;
+    let mut buf = Vec::with_capacity(envelope.encoded_len());
+    // Encoding only fails for a buffer that cannot grow
+    envelope.encode(&mut buf).expect("encode into a Vec");
+    Ok(base64::encode(&buf))
+}
+
+impl ChainBackend for HttpBackend {
+    fn get_account(&self, address: &str) -> Result<Account> {
+        self.block_on(self.api.clone().account(address.to_string()))
+    }
+
+    fn get_height(&self) -> Result<u64> {
+        self.block_on(self.api.clone().height())
+    }
+
+    fn submit_txn(&self, txn: &PaymentTxn) -> Result<String> {
+        self.block_on(self.api.clone().submit(encode(txn))).0
+    }
+
+    fn get_txn_status(&self, hash: &str) -> Result<TxnStatus> {
+        self.block_on(self.api.clone().txn_status(hash.to_string()))
+    }
+
+    fn get_accounts(&self, addresses: &[String]) -> Vec<Result<Account>> {
+        self.map_all(addresses, Api::account)
+    }
+
+    fn submit_txns(&self, txns: &[PaymentTxn]) -> Vec<(Result<String>, Duration)> {
+        let encoded: Vec<Result<String>> = txns.iter().map(encode).collect();
+        let futs: Vec<_> = encoded
+            .into_iter()
+            .map(|e| self.api.clone().submit(e))
+            .collect();
+        self.block_on(join_all(futs))
+    }
+
+    fn get_txn_statuses(&self, hashes: &[String]) -> Vec<Result<TxnStatus>> {
+        self.map_all(hashes, Api::txn_status)
+    }
+
+    fn txn_fee(&self, txn: &PaymentTxn) -> Result<u64> {
+        self.fee_vars()?.dc(txn)
+    }
+
+    fn fee_in_bones(&self, fee: u64) -> Result<u64> {
+        Ok(self.fee_vars()?.dc_to_bones(fee))
+    }
+
+    fn max_payments(&self) -> Result<Option<usize>> {
+        self.block_on(self.api.clone().max_payments())
+    }
+}
+
+impl fmt::Display for HttpBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.api.base_url)
+    }
+}
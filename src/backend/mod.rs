@@ -0,0 +1,92 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use rayon::prelude::*;
+
+use crate::txn::PaymentTxn;
+
+mod http;
+mod sim;
+
+pub use http::HttpBackend;
+pub use sim::{SimBackend, SimConfig};
+
+pub use crate::error::{BankError, Result};
+
+/// The account state `Banker` needs from a chain.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Account {
+    pub address: String,
+    pub balance: u64,
+    pub nonce: u64,
+    pub speculative_nonce: u64,
+}
+
+/// Where a submitted txn currently stands.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TxnStatus {
+    Pending,
+    Cleared(Option<u64>),
+    Failed(String),
+    Unknown,
+}
+
+/// Everything `Banker` reads from or writes to a chain. The live
+/// Helium API is one implementation; test doubles, alternative node
+/// APIs and recording proxies can be plugged in the same way.
+pub trait ChainBackend: fmt::Display + Send + Sync {
+    fn get_account(&self, address: &str) -> Result<Account>;
+
+    fn get_height(&self) -> Result<u64>;
+
+    /// Submits a signed txn, returning its hash.
+    fn submit_txn(&self, txn: &PaymentTxn) -> Result<String>;
+
+    fn get_txn_status(&self, hash: &str) -> Result<TxnStatus>;
+
+    /// Fetches many accounts at once, in the order of `addresses`.
+    /// The default spreads the calls over the current rayon pool;
+    /// backends that can keep many requests in flight override it.
+    fn get_accounts(&self, addresses: &[String]) -> Vec<Result<Account>> {
+        addresses.par_iter().map(|a| self.get_account(a)).collect()
+    }
+
+    /// Submits signed txns at once, returning each hash along with
+    /// how long its submission took.
+    fn submit_txns(&self, txns: &[PaymentTxn]) -> Vec<(Result<String>, Duration)> {
+        txns.par_iter()
+            .map(|txn| {
+                let started = Instant::now();
+                (self.submit_txn(txn), started.elapsed())
+            })
+            .collect()
+    }
+
+    fn get_txn_statuses(&self, hashes: &[String]) -> Vec<Result<TxnStatus>> {
+        hashes.par_iter().map(|h| self.get_txn_status(h)).collect()
+    }
+
+    /// The fee the chain expects in `txn`'s fee field, in DC.
+    fn txn_fee(&self, _txn: &PaymentTxn) -> Result<u64> {
+        Ok(0)
+    }
+
+    /// The bones a payer holding no DC is debited for a `fee` in DC.
+    /// By default a DC costs a bone.
+    fn fee_in_bones(&self, fee: u64) -> Result<u64> {
+        Ok(fee)
+    }
+
+    /// The most payees a payment_v2 may have, when the chain says.
+    fn max_payments(&self) -> Result<Option<usize>> {
+        Ok(None)
+    }
+
+    /// How often it is worth polling for new blocks, if the backend
+    /// knows better than the callers' defaults.
+    fn poll_interval(&self) -> Option<Duration> {
+        None
+    }
+}
@@ -0,0 +1,355 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use super::{Account, BankError, ChainBackend, Result, TxnStatus};
+use crate::txn::PaymentTxn;
+
+/// Blocks a txn with a nonce gap may wait in the mempool before
+/// it is dropped as failed.
+const MAX_PENDING_BLOCKS: u64 = 10;
+
+#[derive(Clone, Debug)]
+pub struct SimConfig {
+    pub block_time: Duration,
+    pub block_capacity: usize,
+    pub fee: u64,
+    /// The most payees a txn may have, as the chain's `max_payments`
+    /// variable sets it.
+    pub max_payments: usize,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            block_time: Duration::from_secs(1),
+            block_capacity: 1000,
+            fee: 0,
+            max_payments: 50,
+        }
+    }
+}
+
+/// An in-process Helium ledger. Blocks are produced lazily whenever
+/// the backend is queried, so no background thread is needed.
+pub struct SimBackend {
+    config: SimConfig,
+    ledger: Mutex<Ledger>,
+}
+
+impl SimBackend {
+    pub fn new(config: SimConfig, genesis: Vec<(String, u64)>) -> Self {
+        let mut ledger = Ledger::new(config.fee, config.max_payments);
+        for (address, bones) in genesis {
+            ledger.credit(&address, bones);
+        }
+        Self {
+            config,
+            ledger: Mutex::new(ledger),
+        }
+    }
+
+    pub fn config(&self) -> &SimConfig {
+        &self.config
+    }
+
+    /// Locks the ledger after producing any blocks that are due.
+    fn ledger(&self) -> MutexGuard<Ledger> {
+        let mut ledger = self.ledger.lock().unwrap();
+        while ledger.last_block.elapsed() >= self.config.block_time {
+            ledger.produce_block(self.config.block_capacity);
+            ledger.last_block += self.config.block_time;
+        }
+        ledger
+    }
+}
+
+impl ChainBackend for SimBackend {
+    fn get_account(&self, address: &str) -> Result<Account> {
+        Ok(self.ledger().account(address))
+    }
+
+    fn get_height(&self) -> Result<u64> {
+        Ok(self.ledger().height)
+    }
+
+    fn submit_txn(&self, txn: &PaymentTxn) -> Result<String> {
+        let hash = txn.hash()?;
+        self.ledger().submit(&hash, txn)?;
+        Ok(hash)
+    }
+
+    fn get_txn_status(&self, hash: &str) -> Result<TxnStatus> {
+        Ok(self
+            .ledger()
+            .statuses
+            .get(hash)
+            .cloned()
+            .unwrap_or(TxnStatus::Unknown))
+    }
+
+    fn txn_fee(&self, _txn: &PaymentTxn) -> Result<u64> {
+        Ok(self.config.fee)
+    }
+
+    fn max_payments(&self) -> Result<Option<usize>> {
+        Ok(Some(self.config.max_payments))
+    }
+
+    fn poll_interval(&self) -> Option<Duration> {
+        Some(self.config.block_time)
+    }
+}
+
+impl fmt::Display for SimBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a simulated ledger ({} ms blocks, {} txns/block, {} bones fee)",
+            self.config.block_time.as_millis(),
+            self.config.block_capacity,
+            self.config.fee,
+        )
+    }
+}
+
+struct PendingTxn {
+    hash: String,
+    txn: PaymentTxn,
+    height: u64,
+}
+
+struct Ledger {
+    fee: u64,
+    max_payments: usize,
+    height: u64,
+    last_block: Instant,
+    // address -> (balance, nonce)
+    accounts: HashMap<String, (u64, u64)>,
+    mempool: VecDeque<PendingTxn>,
+    statuses: HashMap<String, TxnStatus>,
+}
+
+impl Ledger {
+    fn new(fee: u64, max_payments: usize) -> Self {
+        Self {
+            fee,
+            max_payments,
+            height: 1,
+            last_block: Instant::now(),
+            accounts: HashMap::new(),
+            mempool: VecDeque::new(),
+            statuses: HashMap::new(),
+        }
+    }
+
+    fn credit(&mut self, address: &str, bones: u64) {
+        self.accounts.entry(address.to_string()).or_default().0 += bones;
+    }
+
+    fn state(&self, address: &str) -> (u64, u64) {
+        self.accounts.get(address).cloned().unwrap_or_default()
+    }
+
+    fn account(&self, address: &str) -> Account {
+        let (balance, nonce) = self.state(address);
+        let speculative_nonce = self
+            .mempool
+            .iter()
+            .filter(|p| p.txn.payer == address)
+            .map(|p| p.txn.nonce)
+            .max()
+            .unwrap_or(0)
+            .max(nonce);
+
+        Account {
+            address: address.to_string(),
+            balance,
+            nonce,
+            speculative_nonce,
+        }
+    }
+
+    fn submit(&mut self, hash: &str, txn: &PaymentTxn) -> Result<()> {
+        let (_, nonce) = self.state(&txn.payer);
+        if txn.nonce <= nonce {
+            return Err(BankError::Rejected(format!(
+                "invalid nonce: {} for {}, expected {}",
+                txn.nonce,
+                txn.payer,
+                nonce + 1
+            )));
+        }
+        if txn.payees.is_empty() || txn.payees.len() > self.max_payments {
+            return Err(BankError::Rejected(format!(
+                "invalid payment count: {}, expected 1 to {}",
+                txn.payees.len(),
+                self.max_payments
+            )));
+        }
+        if txn.fee < self.fee {
+            return Err(BankError::Rejected(format!(
+                "fee too low: {} below {}",
+                txn.fee, self.fee
+            )));
+        }
+        if self.statuses.contains_key(hash) {
+            return Err(BankError::Rejected(format!("duplicate txn: {}", hash)));
+        }
+
+        self.statuses.insert(hash.to_string(), TxnStatus::Pending);
+        self.mempool.push_back(PendingTxn {
+            hash: hash.to_string(),
+            txn: txn.clone(),
+            height: self.height,
+        });
+        Ok(())
+    }
+
+    /// Includes up to `capacity` valid txns from the mempool. Txns
+    /// waiting on an earlier nonce are retried once it clears.
+    fn produce_block(&mut self, capacity: usize) {
+        self.height += 1;
+        let mut included = 0;
+        let mut progress = true;
+
+        while progress && included < capacity {
+            progress = false;
+            let mut deferred = VecDeque::new();
+
+            while let Some(pending) = self.mempool.pop_front() {
+                if included >= capacity {
+                    deferred.push_back(pending);
+                    continue;
+                }
+
+                let txn = &pending.txn;
+                let (balance, nonce) = self.state(&txn.payer);
+                if txn.nonce <= nonce {
+                    self.fail(&pending.hash, "invalid nonce");
+                } else if txn.nonce > nonce + 1 {
+                    if self.height - pending.height > MAX_PENDING_BLOCKS {
+                        self.fail(&pending.hash, "nonce gap");
+                    } else {
+                        deferred.push_back(pending);
+                    }
+                } else if balance < txn.amount() + txn.fee {
+                    self.fail(&pending.hash, "insufficient funds");
+                } else {
+                    let payer = self.accounts.entry(txn.payer.clone()).or_default();
+                    payer.0 -= txn.amount() + txn.fee;
+                    payer.1 = txn.nonce;
+                    for (address, bones) in &txn.payees {
+                        self.credit(address, *bones);
+                    }
+                    self.statuses
+                        .insert(pending.hash, TxnStatus::Cleared(Some(self.height)));
+                    included += 1;
+                    progress = true;
+                }
+            }
+
+            self.mempool = deferred;
+        }
+    }
+
+    fn fail(&mut self, hash: &str, reason: &str) {
+        self.statuses
+            .insert(hash.to_string(), TxnStatus::Failed(reason.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str = "alice";
+    const BOB: &str = "bob";
+
+    fn ledger() -> Ledger {
+        let mut ledger = Ledger::new(1, 2);
+        ledger.credit(ALICE, 100);
+        ledger
+    }
+
+    fn payment(nonce: u64, bones: u64) -> PaymentTxn {
+        let mut txn = PaymentTxn::new(ALICE, vec![(BOB.to_string(), bones)], nonce);
+        txn.fee = 1;
+        txn
+    }
+
+    #[test]
+    fn test_payment_clears_next_block() {
+        let mut ledger = ledger();
+        ledger.submit("a", &payment(1, 10)).unwrap();
+        assert_eq!(1, ledger.account(ALICE).speculative_nonce);
+        assert_eq!(0, ledger.account(BOB).balance);
+
+        ledger.produce_block(10);
+        assert_eq!(89, ledger.account(ALICE).balance);
+        assert_eq!(10, ledger.account(BOB).balance);
+        assert_eq!(Some(&TxnStatus::Cleared(Some(2))), ledger.statuses.get("a"));
+    }
+
+    #[test]
+    fn test_rejects_stale_nonce_and_low_fee() {
+        let mut ledger = ledger();
+        assert!(ledger.submit("a", &payment(0, 10)).is_err());
+
+        let mut free = payment(1, 10);
+        free.fee = 0;
+        assert!(ledger.submit("b", &free).is_err());
+    }
+
+    #[test]
+    fn test_rejects_too_many_payees() {
+        let mut ledger = ledger();
+        let mut txn = payment(1, 10);
+        txn.payees = vec![(BOB.to_string(), 1); 3];
+        assert!(ledger.submit("a", &txn).is_err());
+        txn.payees.clear();
+        assert!(ledger.submit("a", &txn).is_err());
+    }
+
+    #[test]
+    fn test_out_of_order_nonces_clear_together() {
+        let mut ledger = ledger();
+        ledger.submit("b", &payment(2, 10)).unwrap();
+        ledger.submit("a", &payment(1, 10)).unwrap();
+
+        ledger.produce_block(10);
+        assert_eq!(2, ledger.account(ALICE).nonce);
+        assert_eq!(20, ledger.account(BOB).balance);
+    }
+
+    #[test]
+    fn test_insufficient_funds_fails() {
+        let mut ledger = ledger();
+        ledger.submit("a", &payment(1, 100)).unwrap();
+
+        ledger.produce_block(10);
+        assert_eq!(
+            Some(&TxnStatus::Failed("insufficient funds".to_string())),
+            ledger.statuses.get("a")
+        );
+        assert_eq!(100, ledger.account(ALICE).balance);
+    }
+
+    #[test]
+    fn test_block_capacity() {
+        let mut ledger = ledger();
+        for nonce in 1..=3 {
+            ledger
+                .submit(&nonce.to_string(), &payment(nonce, 1))
+                .unwrap();
+        }
+
+        ledger.produce_block(2);
+        assert_eq!(2, ledger.account(ALICE).nonce);
+        ledger.produce_block(2);
+        assert_eq!(3, ledger.account(ALICE).nonce);
+    }
+}
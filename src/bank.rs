@@ -1,22 +1,48 @@
 use std::{
-    error::Error,
-    fmt, fs,
-    path::PathBuf,
-    str::FromStr,
+    collections::{HashMap, HashSet},
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
     thread,
     time::{Duration, Instant},
 };
 
-use helium_api::{Account, Client, Hnt};
-use helium_wallet::{cmd_create, cmd_pay, cmd_pay::Payee, traits::ReadWrite, wallet::Wallet};
+use helium_wallet::cmd_create;
+use zeroize::Zeroizing;
+
+use crate::amount::{Amount, Budget};
+use crate::backend::{Account, ChainBackend, HttpBackend, TxnStatus};
+use crate::error::{BankError, Result};
+use crate::journal::{Journal, JournalMode, JournalPayment, PlannedPayment};
+use crate::metrics::{Metrics, Summary};
+use crate::nonce::NonceManager;
+use crate::observer::{ConsoleObserver, Event, Observer};
+use crate::plan::{Plan, PlanPayment};
+use crate::profile::{LoadProfile, Progress, Span};
+use crate::rate::{Rate, TokenBucket};
+use crate::report::{BatchReport, PhaseReport, RunConfig, RunLog, RunReport};
+use crate::shutdown::Shutdown;
+use crate::stop::{Checkpoint, StopConditions};
+use crate::topology::{Payments, Topology};
+use crate::tracker::TxnTracker;
+use crate::txn::PaymentTxn;
+use crate::wallets::{KeyFile, WalletStore};
 
 use glob::glob;
-use itertools::Itertools;
+use rand::{rngs::StdRng, SeedableRng};
 use rayon::prelude::*;
+use serde::Serialize;
 
-const MAX_MULTIPAY: usize = 50;
+/// Payees per payment_v2 when neither the chain nor the caller sets
+/// a limit.
+const DEFAULT_MAX_PAYMENTS: usize = 50;
+/// How often waits between blocks check for a shutdown request.
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(200);
 
-#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct Balance {
     pub key_file: String,
     pub address: String,
@@ -24,6 +50,88 @@ pub struct Balance {
     pub error: Option<String>,
 }
 
+/// Totals over a set of balances. Wallets whose balance could not
+/// be fetched are only counted in `errors`.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct BalanceSummary {
+    pub wallets: usize,
+    pub errors: usize,
+    pub total: u64,
+    pub min: u64,
+    pub max: u64,
+    pub median: u64,
+}
+
+impl BalanceSummary {
+    pub fn new(balances: &[Balance]) -> Self {
+        let mut bones: Vec<u64> = balances.iter().filter_map(|b| b.balance).collect();
+        bones.sort_unstable();
+
+        let median = match bones.len() {
+            0 => 0,
+            n if n % 2 == 0 => (bones[n / 2 - 1] + bones[n / 2]) / 2,
+            n => bones[n / 2],
+        };
+
+        Self {
+            wallets: balances.len(),
+            errors: balances.iter().filter(|b| b.error.is_some()).count(),
+            total: bones.iter().sum(),
+            min: bones.first().cloned().unwrap_or(0),
+            max: bones.last().cloned().unwrap_or(0),
+            median,
+        }
+    }
+}
+
+/// Lays out balances one row per wallet followed by their totals,
+/// each labelled in its first column.
+pub fn balance_table(balances: &[Balance], summary: &BalanceSummary) -> prettytable::Table {
+    let mut table = prettytable::Table::new();
+    table.add_row(row!["Key", "Address", "Bones", "Error"]);
+    for b in balances {
+        table.add_row(row![
+            b.key_file,
+            b.address,
+            b.balance.unwrap_or(0),
+            b.error.as_deref().unwrap_or("n/a")
+        ]);
+    }
+    table.add_row(row![
+        "Total",
+        format!("{} wallets", summary.wallets),
+        summary.total,
+        format!("{} errors", summary.errors)
+    ]);
+    table.add_row(row!["Min", "", summary.min, ""]);
+    table.add_row(row!["Max", "", summary.max, ""]);
+    table.add_row(row!["Median", "", summary.median, ""]);
+    table
+}
+
+/// The outcome of one payment the banker submitted.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct PaymentResult {
+    pub payer: String,
+    pub payees: usize,
+    /// Total bones paid out, excluding the fee.
+    pub bones: u64,
+    pub hash: Option<String>,
+    pub error: Option<String>,
+}
+
+impl PaymentResult {
+    fn new(payer: &str, payees: &[(String, u64)], r: &Result<String>) -> Self {
+        Self {
+            payer: payer.to_string(),
+            payees: payees.len(),
+            bones: payees.iter().map(|(_, bones)| bones).sum(),
+            hash: r.as_ref().ok().cloned(),
+            error: r.as_ref().err().map(|e| e.to_string()),
+        }
+    }
+}
+
 pub struct Payment {
     pub payer_key_file: PathBuf,
     pub payees_key_files: Vec<PathBuf>,
@@ -47,14 +155,10 @@ impl Payment {
         }
     }
 
-    pub fn payees(&self) -> Vec<cmd_pay::Payee> {
-        let hnt = Hnt::from_bones(self.bones).to_string();
+    pub fn payees(&self, wallets: &WalletStore) -> Result<Vec<(String, u64)>> {
         self.payees_key_files
             .iter()
-            .map(|kf| {
-                let wallet = Banker::load_wallet(kf);
-                Payee::from_str(&format!("{}={}", wallet.address().unwrap(), hnt)).unwrap()
-            })
+            .map(|kf| Ok((wallets.by_path(kf)?.address.clone(), self.bones)))
             .collect()
     }
 }
@@ -62,137 +166,349 @@ impl Payment {
 impl fmt::Display for Payment {
     // This trait requires `fmt` with this exact signature.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let payer_wallet = Banker::load_wallet(&self.payer_key_file);
-
         write!(
             f,
             "Payment from {} to {:?}.",
-            payer_wallet.address().unwrap(),
-            self.payees()
+            self.payer_key_file.display(),
+            self.payees_key_files
         )
     }
 }
 
-pub struct Banker {
-    api_url: String,
-    password: String,
+/// Who pays whom during sustained load, and how much.
+#[derive(Clone, Debug, Default)]
+pub struct Traffic {
+    pub topology: Topology,
+    pub amount: Amount,
+    /// Seeds every random choice, so a run can be repeated exactly. A
+    /// random seed is picked and reported when not set.
+    pub seed: Option<u64>,
+}
+
+/// Where the password that decrypts the key files comes from.
+#[derive(Clone, Debug)]
+pub enum PasswordSource {
+    Plain(String),
+    /// The name of an env var holding the password.
+    Env(String),
+    /// A file whose first line is the password.
+    File(PathBuf),
+}
+
+impl PasswordSource {
+    fn read(&self) -> Result<Zeroizing<String>> {
+        let password = match self {
+            PasswordSource::Plain(password) => password.clone(),
+            PasswordSource::Env(name) => env::var(name)
+                .map_err(|_| BankError::Config(format!("Missing {} env var.", name)))?,
+            PasswordSource::File(path) => {
+                let contents = Zeroizing::new(
+                    fs::read_to_string(path).map_err(|e| BankError::Io(path.clone(), e))?,
+                );
+                contents.lines().next().unwrap_or_default().to_string()
+            }
+        };
+        Ok(Zeroizing::new(password))
+    }
+}
+
+/// Configures a `Banker`. Without a password, balances can still be
+/// read but every payment fails with `BankError::WrongPassword`.
+#[derive(Clone)]
+pub struct BankerBuilder {
+    api_url: Option<String>,
+    password: PasswordSource,
     working_dir: String,
-    key_paths: Vec<PathBuf>,
+    threads: usize,
+    concurrency: usize,
+    observer: Arc<dyn Observer>,
+    shutdown: Shutdown,
+    drain_timeout: Duration,
+    max_payments: Option<usize>,
 }
 
-impl Banker {
-    pub fn new(api_url: &str, password: &str, working_dir: &str, threads: usize) -> Self {
-        // Set the global threads.  If `0` then uses number of threads equal to logical cores
-        if threads > 0 {
-            rayon::ThreadPoolBuilder::new()
-                .num_threads(threads)
-                .build_global()
-                .unwrap();
-        }
+impl Default for BankerBuilder {
+    fn default() -> Self {
         Self {
-            api_url: api_url.to_string(),
-            password: password.to_string(),
-            working_dir: working_dir.to_string(),
-            key_paths: Self::get_key_paths(working_dir),
+            api_url: None,
+            password: PasswordSource::Plain(String::new()),
+            working_dir: ".".to_string(),
+            threads: 0,
+            concurrency: 256,
+            observer: Arc::new(ConsoleObserver::default()),
+            shutdown: Shutdown::new(),
+            drain_timeout: Duration::from_secs(60),
+            max_payments: None,
+        }
+    }
+}
+
+impl BankerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The Helium API that `build` connects to.
+    pub fn api_url(mut self, api_url: &str) -> Self {
+        self.api_url = Some(api_url.to_string());
+        self
+    }
+
+    pub fn password(mut self, source: PasswordSource) -> Self {
+        self.password = source;
+        self
+    }
+
+    /// The directory holding the key files, `.` by default.
+    pub fn working_dir(mut self, working_dir: &str) -> Self {
+        self.working_dir = working_dir.to_string();
+        self
+    }
+
+    /// The size of the banker's thread pool. `0`, the default, uses
+    /// one thread per logical core.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// How many requests `build` lets the Helium API backend keep in
+    /// flight at once, 256 by default. Unlike `threads` this is not
+    /// tied to the number of cores.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Receives the banker's progress events. Defaults to printing
+    /// them with `ConsoleObserver`.
+    pub fn observer(mut self, observer: Arc<dyn Observer>) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    /// Lets a run be stopped early through `shutdown`, such as from a
+    /// signal handler.
+    pub fn shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// How long to keep waiting on pending txns once a shutdown has
+    /// been requested.
+    pub fn drain_timeout(mut self, drain_timeout: Duration) -> Self {
+        self.drain_timeout = drain_timeout;
+        self
+    }
+
+    /// The most payees to put in one payment, instead of what the
+    /// chain's `max_payments` variable allows.
+    pub fn max_payments(mut self, max_payments: usize) -> Self {
+        self.max_payments = Some(max_payments);
+        self
+    }
+
+    /// Builds a banker talking to the Helium API at `api_url`.
+    pub fn build(self) -> Result<Banker<HttpBackend>> {
+        let api_url = match &self.api_url {
+            Some(api_url) => api_url.clone(),
+            None => return Err(BankError::Config("No API URL set.".to_string())),
+        };
+        let backend = HttpBackend::new(&api_url, self.concurrency)?;
+        self.build_with(backend)
+    }
+
+    /// Builds a banker on any chain backend, such as a `SimBackend`.
+    pub fn build_with<B: ChainBackend>(self, backend: B) -> Result<Banker<B>> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .map_err(|e| BankError::Config(format!("Failed to start threads: {}", e)))?;
+        let password = self.password.read()?;
+        let (key_paths, unreadable) = Banker::<B>::get_key_paths(&self.working_dir)?;
+        let dir = Path::new(&self.working_dir);
+        let wallets = pool.install(|| WalletStore::load_indexed(dir, &key_paths, &password));
+        let max_payments = match self.max_payments {
+            Some(max_payments) => max_payments,
+            None => backend.max_payments()?.unwrap_or(DEFAULT_MAX_PAYMENTS),
+        };
+        if max_payments == 0 {
+            return Err(BankError::Config(
+                "At least one payee per payment is needed.".to_string(),
+            ));
         }
+
+        let banker = Banker {
+            backend,
+            nonces: NonceManager::new(),
+            tracker: TxnTracker::new(),
+            metrics: Metrics::new(),
+            log: RunLog::new(),
+            height: AtomicU64::new(0),
+            accepted: AtomicU64::new(0),
+            spent: AtomicU64::new(0),
+            password,
+            working_dir: self.working_dir,
+            wallets,
+            budget: Budget::new(),
+            pool,
+            observer: self.observer,
+            shutdown: self.shutdown,
+            drain_timeout: self.drain_timeout,
+            max_payments,
+        };
+        for (path, e) in &unreadable {
+            banker.skipped(path.display(), e);
+        }
+        banker.skip_failed();
+        Ok(banker)
+    }
+}
+
+pub struct Banker<B: ChainBackend> {
+    backend: B,
+    nonces: NonceManager,
+    tracker: TxnTracker,
+    metrics: Metrics,
+    log: RunLog,
+    height: AtomicU64,
+    /// Txns the backend accepted, and the bones they spend with fees.
+    accepted: AtomicU64,
+    spent: AtomicU64,
+    password: Zeroizing<String>,
+    working_dir: String,
+    wallets: WalletStore,
+    budget: Budget,
+    pool: rayon::ThreadPool,
+    observer: Arc<dyn Observer>,
+    shutdown: Shutdown,
+    drain_timeout: Duration,
+    max_payments: usize,
+}
+
+impl Banker<HttpBackend> {
+    pub fn builder() -> BankerBuilder {
+        BankerBuilder::new()
     }
+}
 
-    pub fn create_wallets(&self, count: usize) {
+impl<B: ChainBackend> Banker<B> {
+    pub fn create_wallets(&self, count: usize) -> Result<()> {
         for i in 1..=count {
             let n = format!("wallet_{:05}.key", i);
-            let path = PathBuf::from(n);
-            if cmd_create::cmd_basic(&self.password, 2, path.clone(), false, None).is_err() {
-                println!("{:?} already exists.", path.display())
+            let path = PathBuf::from(&self.working_dir).join(n);
+            if path.exists() {
+                self.emit(Event::Skipped {
+                    what: path.display().to_string(),
+                    error: "already exists".to_string(),
+                });
+                continue;
             }
+            cmd_create::cmd_basic(&self.password, 2, path.clone(), false, None).map_err(|e| {
+                BankError::Io(path, io::Error::new(io::ErrorKind::Other, e.to_string()))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Picks up key files added to or removed from the working
+    /// directory since the banker was created, decrypting only the
+    /// new ones.
+    pub fn reload_wallets(&mut self) -> Result<()> {
+        let (key_paths, unreadable) = Self::get_key_paths(&self.working_dir)?;
+        let (wallets, password) = (&mut self.wallets, &self.password);
+        self.pool.install(|| wallets.reload(&key_paths, password));
+        for (path, e) in &unreadable {
+            self.skipped(path.display(), e);
         }
+        self.skip_failed();
+        Ok(())
     }
 
     /// Finds and returns a list of all the keyfiles found
-    /// in the directory.
-    pub fn get_key_paths(dir: &str) -> Vec<PathBuf> {
+    /// in the directory, along with the paths that could not be
+    /// read while looking.
+    pub fn get_key_paths(dir: &str) -> Result<(Vec<PathBuf>, Vec<(PathBuf, BankError)>)> {
         let mut path = PathBuf::from(dir);
         path.push("*.key");
 
         let mut key_paths = vec![];
+        let mut unreadable = vec![];
 
-        for entry in glob(&path.to_string_lossy()).expect("Failed to read glob pattern") {
+        let entries = glob(&path.to_string_lossy())
+            .map_err(|e| BankError::Config(format!("Invalid working dir {}: {}", dir, e)))?;
+        for entry in entries {
             match entry {
-                Ok(path) => {
-                    key_paths.push(path);
-                    //println!("Found wallet: {:?}", path.display());
+                Ok(path) => key_paths.push(path),
+                Err(e) => {
+                    let path = e.path().to_path_buf();
+                    unreadable.push((path.clone(), BankError::Io(path, e.into_error())));
                 }
-                Err(e) => println!("{:?}", e),
             }
         }
 
-        key_paths
+        Ok((key_paths, unreadable))
     }
 
-    /// Loads a wallet from file path
-    pub fn load_wallet(key_file: &PathBuf) -> Wallet {
-        let mut reader = fs::File::open(key_file).unwrap();
-        Wallet::read(&mut reader).unwrap()
+    /// The wallets loaded from the working directory. Key files that
+    /// could not be loaded are left out.
+    pub fn wallets(&self) -> &[KeyFile] {
+        self.wallets.wallets()
     }
 
-    /// Get a list of wallets from key file paths
-    pub fn collect_wallets(&self) -> Vec<Wallet> {
-        self.key_paths
-            .iter()
-            .map(|p| Self::load_wallet(p))
-            .collect()
+    /// The most payees put in one payment.
+    pub fn max_payments(&self) -> usize {
+        self.max_payments
     }
 
-    pub fn wallet_from_address(&self, address: &str) -> Option<Wallet> {
-        self.collect_wallets()
-            .into_iter()
-            .find(|x| x.address().unwrap() == address)
+    pub fn wallet_from_address(&self, address: &str) -> Result<&KeyFile> {
+        self.wallets.by_address(address)
     }
 
-    pub fn get_account(&self, address: &str) -> Option<Account> {
-        let client = Client::new_with_base_url(self.api_url.clone());
-        match client.get_account(&address) {
-            Ok(account) => Some(account),
-            _ => None,
-        }
+    pub fn get_account(&self, address: &str) -> Result<Account> {
+        self.backend.get_account(address)
     }
 
-    pub fn get_account_balance(&self, address: &str) -> u64 {
-        match self.get_account(address) {
-            Some(account) => account.balance,
-            _ => 0,
-        }
+    pub fn get_account_balance(&self, address: &str) -> Result<u64> {
+        Ok(self.get_account(address)?.balance)
     }
 
-    pub fn get_wallet_balance(&self, wallet: &Wallet) -> u64 {
-        self.get_account_balance(&wallet.address().unwrap())
+    pub fn get_wallet_balance(&self, wallet: &KeyFile) -> Result<u64> {
+        self.get_account_balance(&wallet.address)
     }
 
-    /// Returns the wallet with the highest balance
-    pub fn max_bal_wallet(&self) -> Wallet {
-        self.collect_wallets()
+    /// Returns the balance of the wallet with the highest balance
+    pub fn max_bal_wallet(&self) -> Result<Balance> {
+        self.balances()
             .into_iter()
-            .max_by(|x, y| self.get_wallet_balance(x).cmp(&self.get_wallet_balance(y)))
-            .unwrap()
+            .filter(|b| b.balance.is_some())
+            .max_by_key(|b| b.balance)
+            .ok_or_else(|| {
+                BankError::Api(format!(
+                    "no balances could be fetched for the wallets in {}",
+                    self.working_dir
+                ))
+            })
     }
 
-    pub fn print_all_balances(&self) {
-        let mut balances: Vec<Balance> = self
-            .key_paths
-            .par_iter()
-            .map(|p| {
-                let wallet = Self::load_wallet(p);
-                let address = wallet.address().unwrap();
-
-                let client = Client::new_with_base_url(self.api_url.clone());
-
+    /// Fetches the balance of every wallet, sorted by key file. Key
+    /// files that could not be loaded are listed with their error.
+    pub fn balances(&self) -> Vec<Balance> {
+        let wallets = self.wallets();
+        let addresses: Vec<String> = wallets.iter().map(|w| w.address.clone()).collect();
+        let accounts = self.pool.install(|| self.backend.get_accounts(&addresses));
+        let mut balances: Vec<Balance> = wallets
+            .iter()
+            .zip(accounts)
+            .map(|(key, account)| {
                 let mut b = Balance {
-                    key_file: p.to_string_lossy().to_string(),
-                    address: address.clone(),
+                    key_file: key.path.to_string_lossy().to_string(),
+                    address: key.address.clone(),
                     balance: None,
                     error: None,
                 };
 
-                match client.get_account(&address) {
+                match account {
                     Ok(account) => b.balance = Some(account.balance),
                     Err(e) => b.error = Some(e.to_string()),
                 };
@@ -200,162 +516,416 @@ impl Banker {
                 b
             })
             .collect();
+        balances.extend(self.wallets.failed().iter().map(|(path, e)| Balance {
+            key_file: path.to_string_lossy().to_string(),
+            address: String::new(),
+            balance: None,
+            error: Some(e.to_string()),
+        }));
 
         balances.sort();
+        balances
+    }
+
+    /// Runs until one of `stop` is reached, or forever.
+    pub fn fan_out(&self, stop: &StopConditions) -> Result<()> {
+        self.current_height()?;
+        let start = self.checkpoint();
+        let mut batch_num = 1;
+        loop {
+            if self.should_stop(stop, &start) {
+                return Ok(());
+            }
+            self.emit(Event::Balances(self.balances()));
+            self.emit(Event::Message("Fanning out...".to_string()));
+            let now = Instant::now();
+            let mut hashes = vec![];
+
+            for (payer_wallet, payees) in self.fan_out_payments() {
+                if let Ok(hash) = self.submit(payer_wallet, payees) {
+                    hashes.push(hash);
+                }
+            }
+
+            self.log.record_batch(BatchReport {
+                batch: batch_num,
+                height: self.current_height()?,
+                payments: hashes.len(),
+                elapsed_ms: now.elapsed().as_millis() as u64,
+            });
+            batch_num += 1;
 
-        let mut table = prettytable::Table::new();
-        table.add_row(row!["Key", "Address", "Bones", "Error"]);
-        for b in balances {
-            table.add_row(row![
-                b.key_file,
-                b.address,
-                b.balance.unwrap_or(0),
-                b.error.unwrap_or("n/a".to_string())
-            ]);
+            if hashes.is_empty() {
+                self.waiting("Sleeping".to_string());
+                self.wait_unless_stopping(30);
+            } else {
+                self.waiting(format!("Waiting for {} txns to clear", hashes.len()));
+                self.wait_for_txns(&hashes)?;
+            }
         }
-        table.printstd();
     }
 
-    pub fn fan_out(&self) {
-        let wallets = self.collect_wallets();
-        let key_wallet = wallets.first().unwrap();
+    /// One round of fanning out: every wallet pays an even share of
+    /// its balance to each of the others.
+    fn fan_out_payments(&self) -> Vec<(&KeyFile, Vec<(String, u64)>)> {
+        let wallets = self.wallets();
+        let wallet_count: u64 = wallets.len() as u64;
+        let mut payments = vec![];
 
-        loop {
-            self.print_all_balances();
-            println!("Fanning out...");
-            let watch_bal = self.get_wallet_balance(&key_wallet);
-            let wallet_count: u64 = self.key_paths.len() as u64;
-
-            for payer_wallet in self.collect_wallets() {
-                if let Ok(payer_address) = payer_wallet.address() {
-                    let bones = self.get_account_balance(&payer_address) / wallet_count;
-                    let hnt: Hnt = Hnt::from_bones(bones);
-                    if bones > 0 {
-                        println!("Paying out: {} from {}", hnt.to_string(), payer_address);
-                        let payees: Vec<cmd_pay::Payee> = wallets
-                            .iter()
-                            .filter(|w| {
-                                w.address().is_ok() && w.address().unwrap() != payer_address
-                            })
-                            .map(|w| {
-                                Payee::from_str(&format!(
-                                    "{}={}",
-                                    w.address().unwrap(),
-                                    hnt.to_string()
-                                ))
-                                .unwrap()
-                            })
-                            .collect();
-                        for chunk in &payees.into_iter().chunks(MAX_MULTIPAY) {
-                            let now = Instant::now();
-                            let r = cmd_pay::cmd_pay(
-                                self.api_url.clone(),
-                                &payer_wallet,
-                                &self.password,
-                                chunk.collect(),
-                                true,
-                                true,
-                            );
-
-                            println!("Elapsed Time: {} ms.", now.elapsed().as_millis());
-                            println!("Payment result: {:?}", r);
-                        }
-                    }
+        for payer_wallet in wallets {
+            let payer_address = &payer_wallet.address;
+            let addresses: Vec<String> = wallets
+                .iter()
+                .filter(|w| &w.address != payer_address)
+                .map(|w| w.address.clone())
+                .collect();
+            let bones = match self
+                .get_account(payer_address)
+                .and_then(|account| self.share_after_fees(&account, &addresses, wallet_count))
+            {
+                Ok(bones) => bones,
+                Err(e) => {
+                    self.skipped(payer_address, &e);
+                    continue;
                 }
+            };
+            if bones > 0 {
+                self.emit(Event::Paying {
+                    payer: payer_address.clone(),
+                    bones: bones * (wallet_count - 1),
+                });
+                let payees: Vec<(String, u64)> = addresses
+                    .into_iter()
+                    .map(|address| (address, bones))
+                    .collect();
+                for chunk in payees.chunks(self.max_payments) {
+                    payments.push((payer_wallet, chunk.to_vec()));
+                }
+            }
+        }
+        payments
+    }
+
+    /// Groups account by batch_size and pays each other
+    /// waits for blocks, then goes to next group
+    /// at end circles back to beginning and starts over.
+    /// The batch size for each block comes from `profile`, and the
+    /// run ends once the profile's duration has passed or one of
+    /// `stop` is reached. Who pays whom, and how much, follows `traffic`.
+    pub fn pay_forward(
+        &self,
+        profile: &LoadProfile,
+        traffic: &Traffic,
+        stop: &StopConditions,
+    ) -> Result<()> {
+        let (mut payments, mut rng) = self.start_traffic(traffic)?;
+
+        // loop
+        let mut last_height: u64 = self.current_height()?;
+        let mut batch_num = 1;
+        let started = Instant::now();
+        let start = self.checkpoint();
+        loop {
+            if self.should_stop(stop, &start) {
+                break;
+            }
+            let progress = Progress {
+                blocks: batch_num as u64 - 1,
+                elapsed: started.elapsed(),
+            };
+            if profile.finished(&progress) {
+                self.emit(Event::Message(format!(
+                    "Load profile complete after {} batches.",
+                    batch_num - 1
+                )));
+                break;
             }
+            let batch_size = profile.rate(&progress);
+
+            // Take the next batch_size payments, so wallets pay more
+            // than once per block if needed.
+            let payments_batch: Vec<Payment> = payments.by_ref().take(batch_size).collect();
 
+            self.emit(Event::BatchStarted {
+                batch: batch_num,
+                payments: batch_size,
+            });
+            let now = Instant::now();
+            let submitted = self.send_batch(&payments_batch, &traffic.amount, &mut rng);
+            self.emit(Event::BatchFinished {
+                batch: batch_num,
+                payments: submitted,
+                elapsed: now.elapsed(),
+            });
+            self.log.record_batch(BatchReport {
+                batch: batch_num,
+                height: last_height,
+                payments: submitted,
+                elapsed_ms: now.elapsed().as_millis() as u64,
+            });
+
+            // Wait for next block, unless asked to stop meanwhile
             loop {
-                //TODO fix this as balance might remain same
-                if watch_bal != self.get_wallet_balance(&key_wallet) {
+                self.wait_unless_stopping(10);
+                if self.stopping() {
+                    break;
+                }
+                let height = self.current_height()?;
+                if height > last_height {
+                    last_height = height;
                     break;
                 }
-
-                println!("Sleeping...");
-                thread::sleep(Duration::from_secs(30));
             }
+
+            self.poll_txns(&self.tracker.pending())?;
+            let (pending, cleared, failed) = self.tracker.counts();
+            self.emit(Event::Message(format!(
+                "Txns: {} pending, {} cleared, {} failed.",
+                pending, cleared, failed
+            )));
+            batch_num += 1;
         }
+        Ok(())
     }
 
-    /// Groups account by batch_size and pays each other
-    /// waits for blocks, then goes to next group
-    /// at end circles back to beginning and starts over
-    pub fn pay_forward(&self, batch_size: usize) {
-        // let's create payments
-        let mut payments: Vec<Payment> = Vec::with_capacity(self.key_paths.len());
-
-        for (pos, path) in self.key_paths.iter().enumerate() {
-            let payee = if pos + 1 < self.key_paths.len() {
-                self.key_paths[pos + 1].clone()
-            } else {
-                self.key_paths[0].clone()
+    /// Sends `traffic` payments at `rate`, independent of
+    /// block boundaries: arrivals are paced by a token bucket rather
+    /// than by how fast earlier payments went through, and a
+    /// `Lagging` event reports arrivals missed while submission could
+    /// not keep up. Runs until `duration` has passed or one of `stop`
+    /// is reached, or forever.
+    pub fn pay_at_rate(
+        &self,
+        rate: &Rate,
+        duration: Option<Span>,
+        traffic: &Traffic,
+        stop: &StopConditions,
+    ) -> Result<()> {
+        let (mut payments, mut rng) = self.start_traffic(traffic)?;
+
+        let start_height = self.current_height()?;
+        let started = Instant::now();
+        let start = self.checkpoint();
+        let mut bucket = TokenBucket::new(rate, started);
+        let mut last_check = started;
+        let (mut batch_num, mut sent, mut missed) = (1, 0, 0);
+        loop {
+            let progress = Progress {
+                blocks: self
+                    .height
+                    .load(Ordering::Relaxed)
+                    .saturating_sub(start_height),
+                elapsed: started.elapsed(),
             };
+            if duration.map_or(false, |d| d.has_passed(&progress)) || self.should_stop(stop, &start)
+            {
+                break;
+            }
 
-            payments.push(Payment::new_single(path.clone(), payee.clone(), 1));
+            let arrivals = bucket.take(Instant::now());
+            if arrivals == 0 {
+                thread::sleep(bucket.next_token());
+                continue;
+            }
+            let arrived: Vec<Payment> = payments.by_ref().take(arrivals).collect();
+            let now = Instant::now();
+            let submitted = self.send_batch(&arrived, &traffic.amount, &mut rng);
+            self.log.record_batch(BatchReport {
+                batch: batch_num,
+                height: self.height.load(Ordering::Relaxed),
+                payments: submitted,
+                elapsed_ms: now.elapsed().as_millis() as u64,
+            });
+            batch_num += 1;
+            sent += submitted as u64;
+
+            // Check on the chain every few seconds, between arrivals
+            if last_check.elapsed() >= Duration::from_secs(5) {
+                last_check = Instant::now();
+                self.poll_txns(&self.tracker.pending())?;
+                if bucket.missed() > missed {
+                    missed = bucket.missed();
+                    self.emit(Event::Lagging {
+                        sent,
+                        missed,
+                        elapsed: started.elapsed(),
+                    });
+                }
+            }
         }
 
-        // loop
-        let mut last_height: u64 = self.current_height();
-        let mut batch_num = 1;
-        loop {
-            // Group payments into batches of batch_size
-            for payments_batch in payments.chunks(batch_size) {
-                println!("Processing batch #{}...", batch_num);
-                let now = Instant::now();
-                // Parallel process these
-                payments_batch.par_iter().for_each(|p| {
-                    let _ = self.send_payment(p);
-                });
-                println!(
-                    "Processed batch #{} in: {} ms.",
-                    batch_num,
-                    now.elapsed().as_millis()
-                );
+        let elapsed = started.elapsed();
+        self.emit(Event::Message(format!(
+            "Sent {} txns in {:.1}s ({:.1}/s against a target of {}/s), missed {} arrivals.",
+            sent,
+            elapsed.as_secs_f64(),
+            sent as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            rate.tps,
+            bucket.missed()
+        )));
+        Ok(())
+    }
 
-                // Wait for next block
-                loop {
-                    thread::sleep(Duration::from_secs(10));
-                    println!("Checking Height: {}", last_height);
-                    let height = self.current_height();
-                    if height > last_height {
-                        last_height = height;
-                        break;
-                    }
+    /// Starts sending `traffic`: seeds its random choices and learns
+    /// what each wallet can spend. Returns the payments to send and
+    /// the rng to price them with. Fails with fewer than two wallets,
+    /// as no wallet can pay itself.
+    fn start_traffic(&self, traffic: &Traffic) -> Result<(Payments, StdRng)> {
+        if self.wallets.len() < 2 {
+            return Err(BankError::Config(
+                "At least two wallets are needed to send payments.".to_string(),
+            ));
+        }
+        if let Topology::FanOut(width) = traffic.topology {
+            if width > self.max_payments {
+                return Err(BankError::Config(format!(
+                    "A fanout of {} is wider than the {} payees a payment may have.",
+                    width, self.max_payments
+                )));
+            }
+        }
+        let seed = traffic.seed.unwrap_or_else(rand::random);
+        self.emit(Event::Message(format!(
+            "Sending {} payments of {} bones, seed {}.",
+            traffic.topology, traffic.amount, seed
+        )));
+        let mut rng = StdRng::seed_from_u64(seed);
+        let topology_rng = StdRng::from_rng(&mut rng).expect("seed from an StdRng");
+
+        let addresses: Vec<String> = self.wallets().iter().map(|w| w.address.clone()).collect();
+        let accounts = self.pool.install(|| self.backend.get_accounts(&addresses));
+        for (address, account) in addresses.iter().zip(accounts) {
+            match account {
+                Ok(account) => self.budget.set(address, account.balance),
+                Err(e) => self.skipped(address, &e),
+            }
+        }
+
+        let paths = self.wallets().iter().map(|w| w.path.clone()).collect();
+        Ok((traffic.topology.payments(paths, topology_rng), rng))
+    }
+
+    /// Prices `batch` with `amount`, leaving out payments the payer's
+    /// known balance cannot cover along with their fee, and submits
+    /// the rest at once. Returns how many were accepted.
+    fn send_batch(&self, batch: &[Payment], amount: &Amount, rng: &mut StdRng) -> usize {
+        let mut priced = Vec::with_capacity(batch.len());
+        let mut reserved = Vec::with_capacity(batch.len());
+        for payment in batch {
+            let (payer, mut payees) = match self.prepare(payment) {
+                Ok(prepared) => prepared,
+                Err(_) => continue,
+            };
+            let known = self.budget.available(&payer.address).unwrap_or(0);
+            // Priced on the largest amounts and nonce the txn could carry,
+            // so the fee reserved covers the one it is signed with
+            let widest: Vec<(String, u64)> = payees
+                .iter()
+                .map(|(payee, _)| (payee.clone(), known))
+                .collect();
+            let fee = match self.expected_fee(&payer.address, &widest, u64::MAX) {
+                Ok(fee) => fee,
+                Err(e) => {
+                    self.skipped(payment, &e);
+                    continue;
+                }
+            };
+            let bones = amount.sample(rng, known.saturating_sub(fee), payees.len());
+            let total = bones * payees.len() as u64 + fee;
+            if bones == 0 || !self.budget.reserve(&payer.address, total) {
+                let e = BankError::InsufficientFunds {
+                    address: payer.address.clone(),
+                    balance: known,
+                    needed: total.max(payees.len() as u64 + fee),
+                };
+                self.skipped(payment, &e);
+                continue;
+            }
+            payees.iter_mut().for_each(|payee| payee.1 = bones);
+            priced.push((payer, payees));
+            reserved.push(fee);
+        }
+
+        // A txn that costs more than was reserved for it is held back,
+        // so the budget never counts on bones already spent
+        let mut charged = vec![0; priced.len()];
+        let results = self.submit_with(priced.clone(), |i, txn| {
+            charged[i] = self.backend.fee_in_bones(txn.fee)?;
+            if charged[i] > reserved[i] {
+                return Err(BankError::Rejected(format!(
+                    "fee of {} bones is above the {} reserved",
+                    charged[i], reserved[i]
+                )));
+            }
+            Ok(())
+        });
+        let mut submitted = 0;
+        for (i, ((payer, payees), r)) in priced.into_iter().zip(results).enumerate() {
+            match r {
+                Ok((hash, _)) => {
+                    submitted += 1;
+                    // Only the fee the txn was signed with stays reserved
+                    self.budget.refund(&payer.address, reserved[i] - charged[i]);
+                    self.budget
+                        .submitted(&hash, &payer.address, payees, charged[i]);
+                }
+                Err(_) => {
+                    let total: u64 = payees.iter().map(|(_, bones)| bones).sum();
+                    self.budget.refund(&payer.address, total + reserved[i]);
                 }
-                batch_num += 1;
             }
-            // Start all over again.
         }
+        submitted
     }
 
     /// Seeds with independent process, will sleep until
     /// seed accounts are complete.
-    pub fn seed_independent(&self, from_address: &str) {
-        let mut seeder_keys: Vec<PathBuf> = vec![];
-        let mut seedable_keys: Vec<PathBuf> = vec![];
+    /// When resuming, wallets funded by an interrupted run, going by
+    /// its journal, are not seeded again and help seed the rest.
+    pub fn seed_independent(
+        &self,
+        from_address: &str,
+        mode: JournalMode,
+    ) -> Result<Vec<PaymentResult>> {
+        self.wallet_from_address(from_address)?;
+        let journal = self.journal("seed-independent", from_address, mode)?;
+        let funded: HashSet<String> = journal
+            .payments()
+            .into_iter()
+            .filter(JournalPayment::is_paid)
+            .flat_map(|p| p.payees.into_iter().map(|(address, _)| address))
+            .collect();
 
         // One list of payers and one list of receivers
-        for key_path in &self.key_paths {
-            if Self::load_wallet(&key_path).address().unwrap() == from_address {
-                seeder_keys.push(key_path.clone());
-            } else {
-                seedable_keys.push(key_path.clone());
-            }
-        }
+        let (mut seeder_keys, mut seedable_keys): (Vec<&KeyFile>, Vec<&KeyFile>) = self
+            .wallets()
+            .iter()
+            .partition(|key| key.address == from_address || funded.contains(&key.address));
 
-        let total_seedable_keys = seedable_keys.len();
+        let total_seedable_keys = seedable_keys.len() + seeder_keys.len() - 1;
+        let mut batch_num = 1;
+        let mut results = vec![];
 
         // loop and drain the seedable_keys as payments are sent
         while seedable_keys.len() > 0 {
+            if self.stopping() {
+                self.emit(Event::Message(format!(
+                    "Stopping with {} of {} wallets left to seed.",
+                    seedable_keys.len(),
+                    total_seedable_keys
+                )));
+                break;
+            }
             // each seeder will pay a range of receivers
             // this drains the seedable list
-            let mut payments: Vec<(PathBuf, Vec<PathBuf>)> = seeder_keys
+            let mut payments: Vec<(&KeyFile, Vec<&KeyFile>)> = seeder_keys
                 .iter()
-                .map(|p| {
-                    let mut range = MAX_MULTIPAY;
+                .map(|key| {
+                    let mut range = self.max_payments;
                     if range > seedable_keys.len() {
                         range = seedable_keys.len();
                     }
-                    (p.clone(), seedable_keys.drain(..range).collect())
+                    (*key, seedable_keys.drain(..range).collect())
                 })
                 .collect();
 
@@ -363,207 +933,1148 @@ impl Banker {
             payments = payments.into_iter().filter(|p| p.1.len() > 0).collect();
 
             // Lets loop through each payer and pay
-            payments.par_iter().for_each(|payment| {
-                let seed_wallet = Self::load_wallet(&payment.0);
-                let seed_address = seed_wallet.address().unwrap();
-                let seed_bal = self.get_account_balance(&seed_address);
-
-                let wallet_count: u64 = payment.1.len() as u64;
-                let bones = seed_bal / (wallet_count + 1); // plus one is to always keep enough for the seeder account
-
-                let hnt: Hnt = Hnt::from_bones(bones);
-                if bones > 0 {
-                    println!("Paying out: {} from {}", hnt.to_string(), seed_address);
-                    let payees: Vec<cmd_pay::Payee> = payment
-                        .1
-                        .iter()
-                        .map(|p| {
-                            let w = Self::load_wallet(&p);
-                            Payee::from_str(&format!(
-                                "{}={}",
-                                w.address().unwrap(),
-                                hnt.to_string()
-                            ))
-                            .unwrap()
-                        })
-                        .collect();
-
-                    let now = Instant::now();
-                    let r = cmd_pay::cmd_pay(
-                        self.api_url.clone(),
-                        &seed_wallet,
-                        &self.password,
-                        payees,
-                        true,
-                        false,
-                    );
-                    println!("Elapsed Time: {} ms.", now.elapsed().as_millis());
-                    println!("Payment result: {:?}", r);
-                    println!(
-                        "Waiting for txn verification (processed {}/{})...",
-                        seeder_keys.len(),
-                        total_seedable_keys
-                    );
-
-                    let mut last_height = self.current_height();
-                    // only wait if no error
-                    if r.is_ok() {
-                        loop {
-                            if seed_bal != self.get_account_balance(&seed_address) {
-                                break;
-                            }
-                            thread::sleep(Duration::from_secs(15));
-                            let height = self.current_height();
-                            if height > last_height {
-                                last_height = height;
-                                println!("Checking Height: {}", last_height);
-                            }
-                        }
-                    }
-                }
+            let now = Instant::now();
+            let batch: Vec<PaymentResult> = self.pool.install(|| {
+                payments
+                    .par_iter()
+                    .filter_map(|payment| {
+                        self.waiting(format!(
+                            "Waiting for txn verification (processed {}/{})",
+                            seeder_keys.len(),
+                            total_seedable_keys
+                        ));
+                        self.seed_from(payment.0, &payment.1, &journal)
+                            .unwrap_or_else(|e| {
+                                self.skipped(&payment.0.address, &e);
+                                None
+                            })
+                    })
+                    .collect()
+            });
+            let submitted = batch.iter().filter(|r| r.hash.is_some()).count();
+            results.extend(batch);
+            self.log.record_batch(BatchReport {
+                batch: batch_num,
+                height: self.current_height()?,
+                payments: submitted,
+                elapsed_ms: now.elapsed().as_millis() as u64,
             });
+            batch_num += 1;
 
             // All payment txns have been completed
             // copy them to seeder keys so they can help
             // seed next batch.
             payments
                 .iter()
-                .for_each(|payment| seeder_keys.extend(payment.1.iter().cloned()))
+                .for_each(|payment| seeder_keys.extend(payment.1.iter().copied()))
+        }
+        Ok(results)
+    }
+
+    /// Pays each of `payees` an even share of what the `payer` can
+    /// spend after the fee, keeping one share back, and waits for the
+    /// txn to clear. The payment and its outcome are kept in `journal`.
+    fn seed_from(
+        &self,
+        payer: &KeyFile,
+        payees: &[&KeyFile],
+        journal: &Journal,
+    ) -> Result<Option<PaymentResult>> {
+        let seed_address = &payer.address;
+        let account = self.get_account(seed_address)?;
+
+        let wallet_count: u64 = payees.len() as u64;
+        let addresses: Vec<String> = payees.iter().map(|key| key.address.clone()).collect();
+        // plus one is to always keep enough for the seeder account
+        let bones = self.share_after_fees(&account, &addresses, wallet_count + 1)?;
+
+        if bones == 0 {
+            return Ok(None);
+        }
+        self.emit(Event::Paying {
+            payer: seed_address.clone(),
+            bones: bones * wallet_count,
+        });
+        let payees: Vec<(String, u64)> = payees
+            .iter()
+            .map(|key| (key.address.clone(), bones))
+            .collect();
+
+        let ids = journal.plan(vec![PlannedPayment {
+            payer: seed_address.clone(),
+            payees: payees.clone(),
+        }])?;
+        let r = self
+            .submit_journaled(
+                journal,
+                &ids,
+                vec![(payer, payees.clone())],
+                &HashMap::new(),
+            )?
+            .pop()
+            .expect("one result per payment");
+
+        // only wait if no error
+        if let Ok(hash) = &r {
+            self.wait_for_txns(&[hash.clone()])?;
+            self.settle(journal, ids[0], hash)?;
         }
+        Ok(Some(PaymentResult::new(seed_address, &payees, &r)))
     }
 
     /// Will take and evenly distribute funds from either the
-    /// highest balance wallet  or from the `from_address`
-    pub fn seed(&self, from_address: &str) {
-        let seed_wallet = self.wallet_from_address(from_address).unwrap();
+    /// highest balance wallet  or from the `from_address`.
+    /// When resuming, only the payments an interrupted run planned
+    /// but did not get through are sent, for the amounts it planned.
+    pub fn seed(&self, from_address: &str, mode: JournalMode) -> Result<Vec<PaymentResult>> {
+        let seed_wallet = self.wallet_from_address(from_address)?;
+        let journal = self.journal("seed", &seed_wallet.address, mode)?;
+        self.pay_journaled(&journal, || self.seed_payments(seed_wallet))
+    }
 
-        let seed_address = seed_wallet.address().unwrap();
+    /// Plans `seed` without sending anything.
+    pub fn plan_seed(&self, from_address: &str) -> Result<Plan> {
+        let seed_wallet = self.wallet_from_address(from_address)?;
+        let payments = self.seed_payments(seed_wallet)?;
+        self.plan("seed", Some(from_address), vec![payments])
+    }
 
-        let wallet_count: u64 = self.key_paths.len() as u64;
-        let bones = self.get_account_balance(&seed_address) / wallet_count;
+    /// An equal share of what the seed wallet can spend after fees for
+    /// every wallet, in as few payments as possible.
+    fn seed_payments<'a>(
+        &'a self,
+        seed_wallet: &'a KeyFile,
+    ) -> Result<Vec<(&'a KeyFile, Vec<(String, u64)>)>> {
+        let wallets = self.wallets();
+        let seed_address = &seed_wallet.address;
+
+        let wallet_count: u64 = wallets.len() as u64;
+        let account = self.get_account(seed_address)?;
+        let addresses: Vec<String> = wallets
+            .iter()
+            .filter(|w| &w.address != seed_address)
+            .map(|w| w.address.clone())
+            .collect();
+        let bones = self.share_after_fees(&account, &addresses, wallet_count)?;
+        if bones == 0 {
+            return Err(BankError::InsufficientFunds {
+                address: seed_address.clone(),
+                balance: account.balance,
+                needed: wallet_count,
+            });
+        }
 
-        let hnt: Hnt = Hnt::from_bones(bones);
-        if bones > 0 {
-            println!("Paying out: {} from {}", hnt.to_string(), seed_address);
-            let payees: Vec<cmd_pay::Payee> = self
-                .collect_wallets()
-                .iter()
-                .filter(|w| w.address().is_ok() && w.address().unwrap() != seed_address)
-                .map(|w| {
-                    Payee::from_str(&format!("{}={}", w.address().unwrap(), hnt.to_string()))
-                        .unwrap()
-                })
-                .collect();
+        self.emit(Event::Paying {
+            payer: seed_address.clone(),
+            bones: bones * (wallet_count - 1),
+        });
+        let payees: Vec<(String, u64)> = addresses
+            .into_iter()
+            .map(|address| (address, bones))
+            .collect();
+        Ok(payees
+            .chunks(self.max_payments)
+            .map(|chunk| (seed_wallet, chunk.to_vec()))
+            .collect())
+    }
 
-            for chunk in &payees.into_iter().chunks(MAX_MULTIPAY) {
-                //let before_bal = self.get_account_balance(&seed_address);
-
-                let now = Instant::now();
-                let r = cmd_pay::cmd_pay(
-                    self.api_url.clone(),
-                    &seed_wallet,
-                    &self.password,
-                    chunk.collect(),
-                    true,
-                    false,
-                );
-                println!("Elapsed Time: {} ms.", now.elapsed().as_millis());
-                println!("Payment result: {:?}", r);
+    /// Collects all wallet balances into a single wallet.
+    /// When resuming, only the payments an interrupted run planned
+    /// but did not get through are sent.
+    pub fn collect(&self, address: &str, mode: JournalMode) -> Result<Vec<PaymentResult>> {
+        let payee_wallet = self.wallet_from_address(address)?;
+        let journal = self.journal("collect", &payee_wallet.address, mode)?;
+
+        let results = self.pay_journaled(&journal, || Ok(self.collect_payments(payee_wallet)))?;
+        let height = self.current_height()?;
+        self.emit(Event::Message(format!("Current height: {}", height)));
+        Ok(results)
+    }
 
-                // loop {
-                //     if before_bal != self.get_account_balance(&seed_address) {
-                //         break;
-                //     }
-                //     println!("Waiting for txn to process...");
-                //     thread::sleep(Duration::from_secs(30));
-                // }
-            }
-        }
+    /// Plans `collect` without sending anything.
+    pub fn plan_collect(&self, address: &str) -> Result<Plan> {
+        let payee_wallet = self.wallet_from_address(address)?;
+        let payments = self.collect_payments(payee_wallet);
+        self.plan("collect", Some(address), vec![payments])
     }
 
-    /// Collects all wallet balances into a single wallet
-    pub fn collect(&self, address: &str) {
-        self.key_paths.par_iter().for_each(|p| {
-            let payee_wallet = self.wallet_from_address(address).unwrap();
-            let payer_wallet = Self::load_wallet(p);
-            if payer_wallet.address().unwrap() != payee_wallet.address().unwrap() {
-                let bones = self.get_wallet_balance(&payer_wallet);
-                self.pay(bones, &payer_wallet, &payee_wallet)
+    /// A payment of its whole balance, less the fee, from every other
+    /// wallet with one to `payee_wallet`, leaving each empty.
+    fn collect_payments<'a>(
+        &'a self,
+        payee_wallet: &KeyFile,
+    ) -> Vec<(&'a KeyFile, Vec<(String, u64)>)> {
+        let payers: Vec<&KeyFile> = self
+            .wallets()
+            .iter()
+            .filter(|w| w.address != payee_wallet.address)
+            .collect();
+        let addresses: Vec<String> = payers.iter().map(|w| w.address.clone()).collect();
+        let accounts = self.pool.install(|| self.backend.get_accounts(&addresses));
+
+        let mut payments = vec![];
+        for (payer_wallet, account) in payers.into_iter().zip(accounts) {
+            let account = match account {
+                Ok(account) if account.balance == 0 => continue,
+                Ok(account) => account,
+                Err(e) => {
+                    self.skipped(&payer_wallet.address, &e);
+                    continue;
+                }
             };
-        });
-        println!("Current height: {}", self.current_height());
+            match self.sweep_amount(&account, &payee_wallet.address) {
+                Ok(bones) => {
+                    self.emit(Event::Paying {
+                        payer: payer_wallet.address.clone(),
+                        bones,
+                    });
+                    let payees = vec![(payee_wallet.address.clone(), bones)];
+                    payments.push((payer_wallet, payees));
+                }
+                Err(e) => self.skipped(&payer_wallet.address, &e),
+            }
+        }
+        payments
     }
 
-    pub fn send_payment(&self, payment: &Payment) -> Result<(), Box<dyn Error>> {
-        let payer_wallet = Self::load_wallet(&payment.payer_key_file);
-
-        let r = cmd_pay::cmd_pay(
-            self.api_url.clone(),
-            &payer_wallet,
-            &self.password,
-            payment.payees(),
-            true,
-            true,
-        );
+    /// Plans `seed_independent` without sending anything, working out
+    /// each stage from the balances the stages before leave behind.
+    pub fn plan_seed_independent(&self, from_address: &str) -> Result<Plan> {
+        let seeder = self.wallet_from_address(from_address)?;
+        let addresses: Vec<String> = self.wallets().iter().map(|w| w.address.clone()).collect();
+        let fetched = self.pool.install(|| self.backend.get_accounts(&addresses));
+        let mut accounts: HashMap<String, Account> = HashMap::new();
+        for (address, account) in addresses.into_iter().zip(fetched) {
+            let account = account.unwrap_or_else(|e| {
+                self.skipped(&address, &e);
+                Account {
+                    address: address.clone(),
+                    ..Account::default()
+                }
+            });
+            accounts.insert(address, account);
+        }
 
-        if r.is_err() {
-            println!("Payment: {} had an error: {:?}", payment, r);
+        // Mirrors seed_independent: every seeded wallet, paid or not,
+        // helps seed the next stage
+        let mut seeders: Vec<&KeyFile> = vec![seeder];
+        let mut seedable: Vec<&KeyFile> = self
+            .wallets()
+            .iter()
+            .filter(|w| w.address != seeder.address)
+            .collect();
+        let mut stages = vec![];
+        while !seedable.is_empty() {
+            let mut stage = vec![];
+            let mut seeded = vec![];
+            for payer in &seeders {
+                let range = self.max_payments.min(seedable.len());
+                if range == 0 {
+                    break;
+                }
+                let payees: Vec<&KeyFile> = seedable.drain(..range).collect();
+                seeded.extend(payees.iter().copied());
+
+                let addresses: Vec<String> = payees.iter().map(|key| key.address.clone()).collect();
+                let account = &accounts[&payer.address];
+                let bones =
+                    self.share_after_fees(account, &addresses, addresses.len() as u64 + 1)?;
+                if bones == 0 {
+                    continue;
+                }
+                let payees: Vec<(String, u64)> = addresses
+                    .into_iter()
+                    .map(|address| (address, bones))
+                    .collect();
+                let nonce = account.speculative_nonce + 1;
+                let fee = self.expected_fee(&payer.address, &payees, nonce)?;
+                let spent = bones * payees.len() as u64 + fee;
+                let account = accounts
+                    .get_mut(&payer.address)
+                    .expect("every wallet is fetched");
+                account.balance = account.balance.saturating_sub(spent);
+                account.speculative_nonce = nonce;
+                for (payee, bones) in &payees {
+                    accounts
+                        .get_mut(payee)
+                        .expect("every wallet is fetched")
+                        .balance += bones;
+                }
+                stage.push((*payer, payees));
+            }
+            seeders.extend(seeded);
+            if !stage.is_empty() {
+                stages.push(stage);
+            }
         }
+        self.plan("seed-independent", Some(from_address), stages)
+    }
 
-        r
+    /// Plans one round of `fan_out` without sending anything.
+    pub fn plan_fan_out(&self) -> Result<Plan> {
+        let payments = self.fan_out_payments();
+        self.plan("fanout", None, vec![payments])
     }
 
-    // TODO: Refactor this into send_payment
-    pub fn pay(&self, bones: u64, payer: &Wallet, payee: &Wallet) {
-        if bones > 0 {
-            let hnt = Hnt::from_bones(bones);
-            let payer_address = payer.address().unwrap();
-            let payee_address = payee.address().unwrap();
-
-            println!("Sending {} from {}", hnt.to_string(), payer_address);
-            let payee = Payee::from_str(&format!("{}={}", payee_address, hnt.to_string())).unwrap();
-            let now = Instant::now();
-            let r = cmd_pay::cmd_pay(
-                self.api_url.clone(),
-                &payer,
-                &self.password,
-                vec![payee],
-                true,
-                false,
-            );
+    /// A plan for `command` of `stages` of payments, with the fee each
+    /// is expected to cost and the balance of every wallet involved.
+    fn plan(
+        &self,
+        command: &str,
+        address: Option<&str>,
+        stages: Vec<Vec<(&KeyFile, Vec<(String, u64)>)>>,
+    ) -> Result<Plan> {
+        let height = self.current_height()?;
+        let mut addresses: Vec<String> = stages
+            .iter()
+            .flatten()
+            .flat_map(|(payer, payees)| {
+                let payees = payees.iter().map(|(address, _)| address.clone());
+                std::iter::once(payer.address.clone()).chain(payees)
+            })
+            .collect();
+        addresses.sort_unstable();
+        addresses.dedup();
+        let fetched = self.pool.install(|| self.backend.get_accounts(&addresses));
+        let mut accounts = HashMap::new();
+        for (address, account) in addresses.into_iter().zip(fetched) {
+            accounts.insert(address, account?);
+        }
+        let before = accounts
+            .iter()
+            .map(|(address, account)| (address.clone(), account.balance))
+            .collect();
 
-            println!("Elapsed Time: {} ms.", now.elapsed().as_millis());
-            println!("Payment result: {:?}", r);
+        // Each payment is priced with the nonce it would be sent with
+        let mut planned = Vec::with_capacity(stages.len());
+        for stage in stages {
+            let mut payments = Vec::with_capacity(stage.len());
+            for (payer, payees) in stage {
+                let account = accounts
+                    .get_mut(&payer.address)
+                    .expect("every payer is fetched");
+                account.speculative_nonce += 1;
+                let nonce = account.speculative_nonce;
+                payments.push(PlanPayment {
+                    fee: self.expected_fee(&payer.address, &payees, nonce)?,
+                    payer: payer.address.clone(),
+                    payees,
+                });
+            }
+            planned.push(payments);
         }
+        Ok(Plan::new(command, address, height, planned, before))
     }
 
-    pub fn current_height(&self) -> u64 {
-        let client = Client::new_with_base_url(self.api_url.clone());
-        client.get_height().unwrap()
+    /// The bones the chain is expected to charge `payer` in fees to
+    /// pay `payees` with `nonce`.
+    fn expected_fee(&self, payer: &str, payees: &[(String, u64)], nonce: u64) -> Result<u64> {
+        let dc = self
+            .backend
+            .txn_fee(&PaymentTxn::new(payer, payees.to_vec(), nonce))?;
+        self.backend.fee_in_bones(dc)
     }
-}
 
-impl fmt::Display for Banker {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{} wallets, in the \"{}\" directory using {} with {} threads.",
-            self.key_paths.len(),
-            self.working_dir,
-            self.api_url,
-            rayon::current_num_threads(),
-        )
+    /// What `account` can pay each of `payees` when its balance, less
+    /// the fees of paying them `max_payments` at a time, is split
+    /// `shares` ways. Fees are worked out on the share before fees,
+    /// which they can only overestimate.
+    fn share_after_fees(&self, account: &Account, payees: &[String], shares: u64) -> Result<u64> {
+        let before_fees = account.balance / shares;
+        let mut fees = 0;
+        for (i, chunk) in payees.chunks(self.max_payments).enumerate() {
+            let chunk: Vec<(String, u64)> =
+                chunk.iter().map(|a| (a.clone(), before_fees)).collect();
+            let nonce = account.speculative_nonce + 1 + i as u64;
+            fees += self.expected_fee(&account.address, &chunk, nonce)?;
+        }
+        Ok(account.balance.saturating_sub(fees) / shares)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// The most `account` can send `payee` in one payment once its fee
+    /// is paid, leaving nothing behind. Only when the smaller amount
+    /// encodes shorter, and so costs less, can a few bones be left.
+    fn sweep_amount(&self, account: &Account, payee: &str) -> Result<u64> {
+        let nonce = account.speculative_nonce + 1;
+        let fee = |bones| self.expected_fee(&account.address, &[(payee.to_string(), bones)], nonce);
+        let balance = account.balance;
+        let bones = match fee(balance)? {
+            fee if fee < balance => balance - fee,
+            fee => {
+                return Err(BankError::InsufficientFunds {
+                    address: account.address.clone(),
+                    balance,
+                    needed: fee + 1,
+                })
+            }
+        };
+        let exact = balance.saturating_sub(fee(bones)?);
+        if exact > bones && exact + fee(exact)? <= balance {
+            return Ok(exact);
+        }
+        Ok(bones)
+    }
 
-    #[test]
-    fn test_payee_amount() {
-        let addr = "13Ad3bq7UDGYUG7xkKGAQX3vJkWQ3B5ERR3FGhhvqnEktnRNtw2";
-        let bones = 3774430;
+    /// Sends exactly the payments of `plan`, a stage at a time, waiting
+    /// for each stage to clear before sending the next. Nothing is sent
+    /// unless the payers of the first stage still hold what the plan
+    /// has them send. With `fresh`, the journal of an unfinished
+    /// earlier apply is set aside rather than refused.
+    pub fn apply(&self, plan: &Plan, fresh: bool) -> Result<Vec<PaymentResult>> {
+        let mut stages = Vec::with_capacity(plan.stages.len());
+        for stage in &plan.stages {
+            let mut payments = Vec::with_capacity(stage.len());
+            for payment in stage {
+                let payer = self.wallet_from_address(&payment.payer)?;
+                payments.push((payer, payment.payees.clone()));
+            }
+            stages.push(payments);
+        }
+
+        let mut needed: HashMap<&str, u64> = HashMap::new();
+        for payment in plan.stages.first().into_iter().flatten() {
+            *needed.entry(payment.payer.as_str()).or_default() += payment.bones() + payment.fee;
+        }
+        for (address, needed) in needed {
+            let balance = self.get_account_balance(address)?;
+            if balance < needed {
+                return Err(BankError::InsufficientFunds {
+                    address: address.to_string(),
+                    balance,
+                    needed,
+                });
+            }
+        }
+
+        let address = plan.address.as_deref().unwrap_or_default();
+        let mode = if fresh {
+            JournalMode::Fresh
+        } else {
+            JournalMode::New
+        };
+        let journal = self.journal(&format!("apply-{}", plan.command), address, mode)?;
+        let mut results = vec![];
+        let last = stages.len().saturating_sub(1);
+        for (i, payments) in stages.into_iter().enumerate() {
+            if self.stopping() {
+                break;
+            }
+            let queue = self.journal_plan(&journal, payments)?;
+            let fees: HashMap<usize, u64> = queue
+                .iter()
+                .zip(&plan.stages[i])
+                .map(|((id, _, _), payment)| (*id, payment.fee))
+                .collect();
+            let sent = self.send_rounds(&journal, queue, &fees)?;
+            if i < last {
+                let hashes: Vec<(usize, String)> = sent
+                    .iter()
+                    .filter_map(|(id, r)| r.hash.clone().map(|hash| (*id, hash)))
+                    .collect();
+                self.waiting(format!(
+                    "Waiting for stage {} of {} to clear",
+                    i + 1,
+                    last + 1
+                ));
+                let all: Vec<String> = hashes.iter().map(|(_, hash)| hash.clone()).collect();
+                self.wait_for_txns(&all)?;
+                for (id, hash) in &hashes {
+                    self.settle(&journal, *id, hash)?;
+                }
+            }
+            results.extend(sent.into_iter().map(|(_, r)| r));
+        }
+        Ok(results)
+    }
+
+    /// Opens the journal `command` keeps about `address` in the
+    /// working directory. Resuming carries on with the one left by an
+    /// earlier run once its payments are reconciled with the chain;
+    /// otherwise a new one is started, as long as the earlier one is
+    /// finished or set aside.
+    fn journal(&self, command: &str, address: &str, mode: JournalMode) -> Result<Journal> {
+        let path = Journal::path(Path::new(&self.working_dir), command);
+        match mode {
+            JournalMode::New => {
+                // Settle what the earlier run left pending before judging it
+                if let Some(earlier) = Journal::reopen(&path)? {
+                    self.reconcile(&earlier)?;
+                    let unfinished = earlier.unfinished();
+                    if unfinished > 0 {
+                        return Err(BankError::Config(format!(
+                            "{} holds {} payments not known to be paid. Resume it, or start over with --fresh.",
+                            path.display(),
+                            unfinished
+                        )));
+                    }
+                }
+                return Journal::create(&path, command, address);
+            }
+            JournalMode::Fresh => {
+                if path.exists() {
+                    let aside = Journal::set_aside(&path)?;
+                    self.emit(Event::Message(format!(
+                        "Set the earlier journal aside as {}.",
+                        aside.display()
+                    )));
+                }
+                return Journal::create(&path, command, address);
+            }
+            JournalMode::Resume => {}
+        }
+        let journal = Journal::resume(&path, command, address)?;
+        self.emit(Event::Message(format!(
+            "Resuming from {} with {} journaled payments.",
+            path.display(),
+            journal.len()
+        )));
+        self.reconcile(&journal)?;
+        Ok(journal)
+    }
+
+    /// Settles the journaled payments that were sent but never seen
+    /// to clear or fail, waiting on those the chain still has pending.
+    /// One the chain does not know of counts as paid only if its payer
+    /// has since used the nonce it was signed with.
+    fn reconcile(&self, journal: &Journal) -> Result<()> {
+        let mut unsettled: Vec<(usize, JournalPayment)> = journal
+            .payments()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, p)| p.is_unsettled())
+            .collect();
+        if unsettled.is_empty() {
+            return Ok(());
+        }
+        self.emit(Event::Message(format!(
+            "Reconciling {} journaled payments with the chain.",
+            unsettled.len()
+        )));
+
+        let height = self.current_height()?;
+        for (_, payment) in &unsettled {
+            let (hash, _) = payment
+                .submitted
+                .as_ref()
+                .expect("unsettled means submitted");
+            self.tracker.record(hash, &payment.payer, height);
+        }
+        loop {
+            let hashes: Vec<String> = unsettled
+                .iter()
+                .filter_map(|(_, p)| p.submitted.as_ref().map(|(hash, _)| hash.clone()))
+                .collect();
+            self.poll_txns(&hashes)?;
+
+            let mut pending = vec![];
+            for (id, payment) in unsettled {
+                let (hash, nonce) = payment
+                    .submitted
+                    .clone()
+                    .expect("unsettled means submitted");
+                match self.tracker.status(&hash) {
+                    Some(TxnStatus::Cleared(_)) => journal.resolved(id, true)?,
+                    Some(TxnStatus::Failed(_)) => journal.resolved(id, false)?,
+                    Some(TxnStatus::Pending) => pending.push((id, payment)),
+                    _ => {
+                        let used = self.get_account(&payment.payer)?.nonce >= nonce;
+                        journal.resolved(id, used)?;
+                    }
+                }
+            }
+            if pending.is_empty() || self.stopping() {
+                return Ok(());
+            }
+            unsettled = pending;
+            self.waiting(format!(
+                "Waiting for {} journaled txns to clear",
+                unsettled.len()
+            ));
+            self.wait(15);
+        }
+    }
+
+    /// Sends the payments planned in `journal` that have not been
+    /// paid, first planning them with `plan` when it holds none.
+    fn pay_journaled<'a>(
+        &'a self,
+        journal: &Journal,
+        plan: impl FnOnce() -> Result<Vec<(&'a KeyFile, Vec<(String, u64)>)>>,
+    ) -> Result<Vec<PaymentResult>> {
+        let queue = if journal.is_empty() {
+            self.journal_plan(journal, plan()?)?
+        } else {
+            let mut queue = vec![];
+            for (id, payment) in journal.payments().into_iter().enumerate() {
+                if !payment.is_paid() && !payment.is_unsettled() {
+                    queue.push((
+                        id,
+                        self.wallet_from_address(&payment.payer)?,
+                        payment.payees,
+                    ));
+                }
+            }
+            self.emit(Event::Message(format!(
+                "{} of {} journaled payments left to send.",
+                queue.len(),
+                journal.len()
+            )));
+            queue
+        };
+        let sent = self.send_rounds(journal, queue, &HashMap::new())?;
+        Ok(sent.into_iter().map(|(_, r)| r).collect())
+    }
+
+    /// Records `payments` in `journal`, pairing each with its id.
+    fn journal_plan<'a>(
+        &self,
+        journal: &Journal,
+        payments: Vec<(&'a KeyFile, Vec<(String, u64)>)>,
+    ) -> Result<Vec<(usize, &'a KeyFile, Vec<(String, u64)>)>> {
+        let planned = payments
+            .iter()
+            .map(|(payer, payees)| PlannedPayment {
+                payer: payer.address.clone(),
+                payees: payees.clone(),
+            })
+            .collect();
+        let ids = journal.plan(planned)?;
+        Ok(ids
+            .into_iter()
+            .zip(payments)
+            .map(|(id, (payer, payees))| (id, payer, payees))
+            .collect())
+    }
+
+    /// Sends the journaled payments in `queue`, returning how each
+    /// went by id. Each payer has one payment in flight at a time, so
+    /// its nonces reach the chain in order. Payments with a fee in
+    /// `max_fees`, by id, are not sent for any more than that.
+    fn send_rounds(
+        &self,
+        journal: &Journal,
+        mut queue: Vec<(usize, &KeyFile, Vec<(String, u64)>)>,
+        max_fees: &HashMap<usize, u64>,
+    ) -> Result<Vec<(usize, PaymentResult)>> {
+        let mut results = vec![];
+        while !queue.is_empty() {
+            if self.stopping() {
+                self.emit(Event::Message(format!(
+                    "Stopping with {} payments left to send.",
+                    queue.len()
+                )));
+                break;
+            }
+            let mut payers = HashSet::new();
+            let (round, rest): (Vec<_>, Vec<_>) = queue
+                .into_iter()
+                .partition(|(_, payer, _)| payers.insert(payer.address.clone()));
+            queue = rest;
+
+            let ids: Vec<usize> = round.iter().map(|(id, _, _)| *id).collect();
+            let payments: Vec<(&KeyFile, Vec<(String, u64)>)> = round
+                .into_iter()
+                .map(|(_, payer, payees)| (payer, payees))
+                .collect();
+            let submitted = self.submit_journaled(journal, &ids, payments.clone(), max_fees)?;
+            results.extend(ids.into_iter().zip(payments.iter().zip(submitted)).map(
+                |(id, ((payer, payees), r))| (id, PaymentResult::new(&payer.address, payees, &r)),
+            ));
+        }
+        Ok(results)
+    }
+
+    /// Submits `payments`, planned in `journal` as `ids`. Each txn's
+    /// hash and nonce are journaled before it is sent, so one a crash
+    /// cuts off is reconciled on resume rather than paid again. Txns
+    /// the chain refuses, or that cost more than their fee in
+    /// `max_fees`, are journaled as rejected.
+    fn submit_journaled(
+        &self,
+        journal: &Journal,
+        ids: &[usize],
+        payments: Vec<(&KeyFile, Vec<(String, u64)>)>,
+        max_fees: &HashMap<usize, u64>,
+    ) -> Result<Vec<Result<String>>> {
+        let results = self.submit_with(payments, |i, txn| {
+            if let Some(&max_fee) = max_fees.get(&ids[i]) {
+                let fee = self.backend.fee_in_bones(txn.fee)?;
+                if fee > max_fee {
+                    return Err(BankError::Rejected(format!(
+                        "fee of {} bones is above the {} planned",
+                        fee, max_fee
+                    )));
+                }
+            }
+            journal.submitted(ids[i], txn.hash()?, txn.nonce)
+        });
+        ids.iter()
+            .zip(results)
+            .map(|(id, r)| {
+                // Any other error may have reached the chain, so the
+                // txn is left for its nonce to settle
+                if let Err(e @ BankError::Rejected(_)) = &r {
+                    journal.rejected(*id, e)?;
+                }
+                Ok(r.map(|(hash, _)| hash))
+            })
+            .collect()
+    }
+
+    /// Journals how payment `id`, sent as `hash`, ended, if the chain
+    /// has settled it.
+    fn settle(&self, journal: &Journal, id: usize, hash: &str) -> Result<()> {
+        match self.tracker.status(hash) {
+            Some(TxnStatus::Cleared(_)) => journal.resolved(id, true),
+            Some(TxnStatus::Failed(_)) => journal.resolved(id, false),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn send_payment(&self, payment: &Payment) -> Result<String> {
+        let (payer_wallet, payees) = self.prepare(payment)?;
+        self.submit(payer_wallet, payees)
+    }
+
+    /// Looks up the loaded payer and payees of `payment`, reporting
+    /// it as skipped if one of them is not loaded.
+    fn prepare(&self, payment: &Payment) -> Result<(&KeyFile, Vec<(String, u64)>)> {
+        let prepared = self
+            .wallets
+            .by_path(&payment.payer_key_file)
+            .and_then(|payer_wallet| Ok((payer_wallet, payment.payees(&self.wallets)?)));
+        if let Err(e) = &prepared {
+            self.skipped(payment, e);
+        }
+        prepared
+    }
+
+    // TODO: Refactor this into send_payment
+    pub fn pay(&self, bones: u64, payer: &KeyFile, payee: &KeyFile) -> Result<String> {
+        self.emit(Event::Paying {
+            payer: payer.address.clone(),
+            bones,
+        });
+        self.submit(payer, vec![(payee.address.clone(), bones)])
+    }
+
+    /// Signs a payment_v2 from `payer` using its next locally tracked
+    /// nonce and submits it to the backend, returning the txn hash.
+    /// A rejected submission resyncs the payer's nonce from the chain.
+    pub fn submit(&self, payer: &KeyFile, payees: Vec<(String, u64)>) -> Result<String> {
+        self.submit_all(vec![(payer, payees)])
+            .pop()
+            .expect("one result per payment")
+    }
+
+    /// Signs every payment in parallel and hands them to the backend
+    /// in one go, so it can keep them all in flight at once. Returns
+    /// the txn hashes in the order of `payments`.
+    pub fn submit_all(&self, payments: Vec<(&KeyFile, Vec<(String, u64)>)>) -> Vec<Result<String>> {
+        self.submit_signed(payments)
+            .into_iter()
+            .map(|r| r.map(|(hash, _)| hash))
+            .collect()
+    }
+
+    /// Does the work of `submit_all`, also returning the nonce each
+    /// accepted txn was signed with.
+    fn submit_signed(
+        &self,
+        payments: Vec<(&KeyFile, Vec<(String, u64)>)>,
+    ) -> Vec<Result<(String, u64)>> {
+        self.submit_with(payments, |_, _| Ok(()))
+    }
+
+    /// Does the work of `submit_signed`, first calling `before` with
+    /// the index and signed txn of each payment. A payment `before`
+    /// fails is not submitted, and its payer's nonce is resynced.
+    fn submit_with<F>(
+        &self,
+        payments: Vec<(&KeyFile, Vec<(String, u64)>)>,
+        mut before: F,
+    ) -> Vec<Result<(String, u64)>>
+    where
+        F: FnMut(usize, &PaymentTxn) -> Result<()>,
+    {
+        let started = Instant::now();
+        let payers: Vec<String> = payments.iter().map(|(p, _)| p.address.clone()).collect();
+        let signed: Vec<Result<PaymentTxn>> = self.pool.install(|| {
+            payments
+                .into_par_iter()
+                .map(|(payer, payees)| self.sign(payer, payees))
+                .collect()
+        });
+        let signed: Vec<Result<PaymentTxn>> = signed
+            .into_iter()
+            .enumerate()
+            .map(|(i, r)| {
+                r.and_then(|txn| match before(i, &txn) {
+                    Ok(()) => Ok(txn),
+                    Err(e) => {
+                        // The nonce it was signed with goes unused
+                        self.nonces.resync(&payers[i]);
+                        Err(e)
+                    }
+                })
+            })
+            .collect();
+        let txns: Vec<PaymentTxn> = signed
+            .iter()
+            .filter_map(|r| r.as_ref().ok())
+            .cloned()
+            .collect();
+        let mut submitted = self
+            .pool
+            .install(|| self.backend.submit_txns(&txns))
+            .into_iter();
+
+        payers
+            .iter()
+            .zip(signed)
+            .map(|(address, signed)| {
+                let mut nonce = 0;
+                let r = signed.and_then(|txn| {
+                    nonce = txn.nonce;
+                    let (r, latency) = submitted.next().expect("one result per txn");
+                    let error = r.as_ref().err().map(|e| e.to_string());
+                    self.metrics.record_submit(latency, error.as_deref());
+                    if r.is_ok() {
+                        self.accepted.fetch_add(1, Ordering::Relaxed);
+                        let fee = self.backend.fee_in_bones(txn.fee).unwrap_or(txn.fee);
+                        self.spent.fetch_add(txn.amount() + fee, Ordering::Relaxed);
+                    }
+                    r
+                });
+                self.submitted(address, &r, started.elapsed());
+                r.map(|hash| (hash, nonce))
+            })
+            .collect()
+    }
+
+    /// Builds a payment_v2 from `payer` with its next locally tracked
+    /// nonce and the fee the chain expects for it, and signs it.
+    fn sign(&self, payer: &KeyFile, payees: Vec<(String, u64)>) -> Result<PaymentTxn> {
+        if payees.is_empty() || payees.len() > self.max_payments {
+            return Err(BankError::Rejected(format!(
+                "a payment needs 1 to {} payees, not {}",
+                self.max_payments,
+                payees.len()
+            )));
+        }
+        let keypair = payer.keypair()?;
+        let address = &payer.address;
+
+        let nonce = self.nonces.next(address, || {
+            Ok(self.backend.get_account(address)?.speculative_nonce)
+        })?;
+        let mut txn = PaymentTxn::new(address, payees, nonce);
+        // The fee depends on the nonce's encoding, so it comes second.
+        // A nonce handed out but never used must be given back.
+        txn.fee = match self.backend.txn_fee(&txn) {
+            Ok(fee) => fee,
+            Err(e) => {
+                self.nonces.resync(address);
+                return Err(e);
+            }
+        };
+        txn.sign(keypair)?;
+        Ok(txn)
+    }
+
+    /// Tracks a submitted txn. A rejected payment resyncs the payer's
+    /// nonce from the chain.
+    fn submitted(&self, address: &str, r: &Result<String>, elapsed: Duration) {
+        match r {
+            Ok(hash) => {
+                let height = match self.height.load(Ordering::Relaxed) {
+                    0 => self.current_height().unwrap_or_default(),
+                    height => height,
+                };
+                self.tracker.record(hash, address, height);
+                self.emit(Event::PaymentSubmitted {
+                    payer: address.to_string(),
+                    hash: hash.clone(),
+                    elapsed,
+                });
+            }
+            Err(e) => {
+                self.log.record_rejected(address, &e.to_string());
+                self.nonces.resync(address);
+                self.emit(Event::PaymentRejected {
+                    payer: address.to_string(),
+                    error: e.to_string(),
+                    elapsed,
+                });
+            }
+        }
+    }
+
+    /// The last known status of a txn submitted by this banker.
+    pub fn txn_status(&self, hash: &str) -> Option<TxnStatus> {
+        self.tracker.status(hash)
+    }
+
+    /// Refreshes the status of `hashes` from the backend. A failed
+    /// txn resyncs its payer's nonce.
+    pub fn poll_txns(&self, hashes: &[String]) -> Result<()> {
+        let height = self.current_height()?;
+        let statuses = self.pool.install(|| self.backend.get_txn_statuses(hashes));
+        for (hash, status) in hashes.iter().zip(statuses) {
+            let status = match status {
+                Ok(status) => status,
+                Err(e) => {
+                    self.emit(Event::Message(format!(
+                        "Failed to get status of {}: {}",
+                        hash, e
+                    )));
+                    continue;
+                }
+            };
+            if let Some(txn) = self.tracker.update(hash, status, height) {
+                self.metrics.record_resolved(&txn, height);
+                match txn.status {
+                    TxnStatus::Cleared(height) => {
+                        self.budget.resolved(hash, true);
+                        self.emit(Event::PaymentConfirmed {
+                            hash: hash.clone(),
+                            height,
+                        });
+                    }
+                    TxnStatus::Failed(reason) => {
+                        self.budget.resolved(hash, false);
+                        self.nonces.resync(&txn.payer);
+                        self.emit(Event::PaymentFailed {
+                            hash: hash.clone(),
+                            reason,
+                        });
+                    }
+                    TxnStatus::Pending | TxnStatus::Unknown => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Latency and throughput measurements of everything submitted so far.
+    pub fn metrics(&self) -> Summary {
+        self.metrics.summary()
+    }
+
+    /// Waits until every wallet holds at least `min_bones`, giving up
+    /// once `timeout` has passed. Returns whether all were funded.
+    pub fn wait_until_funded(&self, min_bones: u64, timeout: Option<Span>) -> Result<bool> {
+        let started = Instant::now();
+        let start_height = self.current_height()?;
+        loop {
+            let unfunded = self
+                .balances()
+                .iter()
+                .filter(|b| b.balance.unwrap_or(0) < min_bones)
+                .count();
+            if unfunded == 0 {
+                return Ok(true);
+            }
+            if self.stopping() {
+                return Ok(false);
+            }
+
+            let progress = Progress {
+                blocks: self.current_height()? - start_height,
+                elapsed: started.elapsed(),
+            };
+            if timeout.map_or(false, |t| t.has_passed(&progress)) {
+                return Ok(false);
+            }
+            self.waiting(format!("Waiting for {} wallets to be funded", unfunded));
+            self.wait(30);
+        }
+    }
+
+    pub fn record_phase(&self, phase: PhaseReport) {
+        self.log.record_phase(phase);
+    }
+
+    /// Builds the report for everything this banker has done so far.
+    /// `command` describes the subcommand and its options.
+    pub fn report(&self, command: serde_json::Value) -> RunReport {
+        let config = RunConfig {
+            command,
+            backend: self.backend.to_string(),
+            working_dir: self.working_dir.clone(),
+            threads: self.pool.current_num_threads(),
+            wallets: self.wallets.len(),
+        };
+        // A run that stopped because the API went away still gets a report
+        let end_height = self
+            .current_height()
+            .unwrap_or_else(|_| self.height.load(Ordering::Relaxed));
+        self.log.report(
+            config,
+            end_height,
+            &self.tracker.all(),
+            self.metrics.summary(),
+        )
+    }
+
+    /// Polls until every txn in `hashes` has cleared or failed. Once
+    /// a shutdown has been requested it gives up when the drain
+    /// timeout has passed since that first request.
+    pub fn wait_for_txns(&self, hashes: &[String]) -> Result<()> {
+        loop {
+            self.poll_txns(hashes)?;
+            if hashes.iter().all(|h| self.tracker.is_resolved(h)) {
+                return Ok(());
+            }
+            if let Some(since) = self.shutdown.requested_at() {
+                if since.elapsed() >= self.drain_timeout {
+                    return Ok(());
+                }
+            }
+            self.wait(15);
+        }
+    }
+
+    /// Whether a shutdown has been requested. Commands stop
+    /// scheduling payments once it has.
+    pub fn stopping(&self) -> bool {
+        self.shutdown.requested()
+    }
+
+    /// Waits for every txn still pending to clear or fail, so the
+    /// report shows how they ended. After a shutdown request this
+    /// gives up once the drain timeout has passed since the request,
+    /// however much of it earlier waits used up.
+    pub fn drain(&self) -> Result<()> {
+        let pending = self.tracker.pending();
+        if pending.is_empty() {
+            return Ok(());
+        }
+        self.waiting(format!("Draining {} pending txns", pending.len()));
+        self.wait_for_txns(&pending)?;
+        let (pending, cleared, failed) = self.tracker.counts();
+        self.emit(Event::Message(format!(
+            "Txns: {} pending, {} cleared, {} failed.",
+            pending, cleared, failed
+        )));
+        Ok(())
+    }
+
+    /// Fetches the chain height, emitting `BlockSeen` when it is
+    /// higher than any height seen before.
+    pub fn current_height(&self) -> Result<u64> {
+        let height = self.backend.get_height()?;
+        let previous = self.height.fetch_max(height, Ordering::Relaxed);
+        if previous != 0 && height > previous {
+            self.emit(Event::BlockSeen { height });
+        }
+        self.log.saw_height(height);
+        Ok(height)
+    }
+
+    /// Where this banker stands, going by the last height seen.
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            at: Instant::now(),
+            height: self.height.load(Ordering::Relaxed),
+            txns: self.accepted.load(Ordering::Relaxed),
+            spent: self.spent.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Whether a run that started at `start` has reached one of
+    /// `stop`, saying why when it has.
+    fn should_stop(&self, stop: &StopConditions, start: &Checkpoint) -> bool {
+        if self.stopping() {
+            self.emit(Event::Message("Stopping, shutdown requested.".to_string()));
+            return true;
+        }
+        match stop.reached(start, &self.checkpoint()) {
+            Some(reason) => {
+                self.emit(Event::Message(format!("Stopping, {}.", reason)));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn emit(&self, event: Event) {
+        self.observer.on_event(&event);
+    }
+
+    /// Reports every key file the wallet store could not load.
+    fn skip_failed(&self) {
+        for (path, e) in self.wallets.failed() {
+            self.skipped(path.display(), e);
+        }
+    }
+
+    fn waiting(&self, reason: String) {
+        self.emit(Event::Waiting { reason });
+    }
+
+    fn skipped(&self, what: impl fmt::Display, error: &BankError) {
+        self.emit(Event::Skipped {
+            what: what.to_string(),
+            error: error.to_string(),
+        });
+    }
+
+    /// Sleeps for `secs`, or less if the backend produces blocks faster.
+    fn wait(&self, secs: u64) {
+        thread::sleep(self.wait_time(secs));
+    }
+
+    /// Does the work of `wait`, returning early once a shutdown has
+    /// been requested so none of the drain timeout is slept away.
+    fn wait_unless_stopping(&self, secs: u64) {
+        let until = Instant::now() + self.wait_time(secs);
+        while !self.stopping() {
+            let left = until.saturating_duration_since(Instant::now());
+            if left == Duration::from_secs(0) {
+                return;
+            }
+            thread::sleep(left.min(STOP_CHECK_INTERVAL));
+        }
+    }
+
+    fn wait_time(&self, secs: u64) -> Duration {
+        let duration = Duration::from_secs(secs);
+        match self.backend.poll_interval() {
+            Some(interval) => duration.min(interval),
+            None => duration,
+        }
+    }
+}
+
+impl<B: ChainBackend> fmt::Display for Banker<B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} wallets, in the \"{}\" directory using {} with {} threads, paying up to {} payees at once.",
+            self.wallets.len(),
+            self.working_dir,
+            self.backend,
+            self.pool.current_num_threads(),
+            self.max_payments,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{SimBackend, SimConfig};
+    use crate::observer::RecordingObserver;
+    use crate::profile::Shape;
+    use helium_api::Hnt;
+    use helium_wallet::cmd_pay::Payee;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_payee_amount() {
+        let addr = "13Ad3bq7UDGYUG7xkKGAQX3vJkWQ3B5ERR3FGhhvqnEktnRNtw2";
+        let bones = 3774430;
 
         let payee = Payee::from_str(&format!("{}={}", addr, bones));
 
@@ -575,4 +2086,647 @@ mod tests {
         let hnt = Hnt::from_bones(203130111);
         assert_eq!("2.03130111", format!("{}", hnt.to_string()));
     }
+
+    #[test]
+    fn test_balance_summary() {
+        let balance = |bones: Option<u64>| Balance {
+            key_file: String::new(),
+            address: String::new(),
+            balance: bones,
+            error: bones.map_or(Some("offline".to_string()), |_| None),
+        };
+        let balances = vec![
+            balance(Some(40)),
+            balance(Some(10)),
+            balance(None),
+            balance(Some(30)),
+            balance(Some(20)),
+        ];
+
+        assert_eq!(
+            BalanceSummary {
+                wallets: 5,
+                errors: 1,
+                total: 100,
+                min: 10,
+                max: 40,
+                median: 25,
+            },
+            BalanceSummary::new(&balances)
+        );
+        assert_eq!(BalanceSummary::default(), BalanceSummary::new(&[]));
+
+        // The totals follow the wallets' rows in CSV too, labelled
+        let summary = BalanceSummary::new(&balances);
+        let mut csv = vec![];
+        balance_table(&balances, &summary).to_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(10, lines.len());
+        assert_eq!("Key,Address,Bones,Error", lines[0]);
+        assert_eq!(
+            vec![
+                "Total,5 wallets,100,1 errors",
+                "Min,,10,",
+                "Max,,40,",
+                "Median,,25,"
+            ],
+            lines[6..].to_vec()
+        );
+    }
+
+    #[test]
+    fn test_seed_independent_sim() {
+        let dir = tempfile::tempdir().unwrap();
+        let seeder = create_key_files(dir.path(), 4).remove(0);
+        let backend = SimBackend::new(fast_sim_config(0), vec![(seeder.clone(), 1_000_000)]);
+        let recorder = Arc::new(RecordingObserver::new());
+        let banker = test_builder(dir.path())
+            .observer(recorder.clone())
+            .build_with(backend)
+            .unwrap();
+        let results = banker.seed_independent(&seeder, JournalMode::New).unwrap();
+
+        for wallet in banker.wallets() {
+            assert!(banker.get_wallet_balance(&wallet).unwrap() > 0);
+        }
+
+        assert!(results.iter().all(|r| r.error.is_none()));
+        let events = recorder.events();
+        let count = |f: fn(&Event) -> bool| events.iter().filter(|e| f(e)).count();
+        assert_eq!(
+            results.len(),
+            count(|e| matches!(e, Event::PaymentSubmitted { .. }))
+        );
+        assert_eq!(
+            results.len(),
+            count(|e| matches!(e, Event::PaymentConfirmed { .. }))
+        );
+        let report = banker.report(serde_json::Value::Null);
+        let batched: usize = report.batches.iter().map(|b| b.payments).sum();
+        assert_eq!(results.len(), batched);
+    }
+
+    #[test]
+    fn test_pay_at_rate_sim() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Arc::new(RecordingObserver::new());
+        let banker = funded_sim_banker(dir.path(), 2, 1_000, recorder.clone());
+        let rate = Rate::new(40.0, Some(4)).unwrap();
+        let stop = StopConditions {
+            max_txns: Some(10),
+            ..StopConditions::default()
+        };
+        banker
+            .pay_at_rate(&rate, None, &Traffic::default(), &stop)
+            .unwrap();
+
+        // Checked between takes from the bucket, each at most a burst
+        let events = recorder.events();
+        let count = |f: fn(&Event) -> bool| events.iter().filter(|e| f(e)).count();
+        let sent = count(|e| matches!(e, Event::PaymentSubmitted { .. }));
+        assert!((10..=13).contains(&sent), "sent {}", sent);
+        assert_eq!(0, count(|e| matches!(e, Event::PaymentRejected { .. })));
+    }
+
+    #[test]
+    fn test_pay_at_rate_counts_only_what_it_sent() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Arc::new(RecordingObserver::new());
+        let banker = funded_sim_banker(dir.path(), 2, 10, recorder.clone());
+        let traffic = Traffic {
+            amount: Amount::Fixed(4),
+            ..Traffic::default()
+        };
+        let rate = Rate::new(20.0, None).unwrap();
+        banker
+            .pay_at_rate(
+                &rate,
+                Some(Span::Secs(1)),
+                &traffic,
+                &StopConditions::default(),
+            )
+            .unwrap();
+
+        // Most arrivals are skipped once the 10 bones each run out
+        let events = recorder.events();
+        let submitted = events
+            .iter()
+            .filter(|e| matches!(e, Event::PaymentSubmitted { .. }))
+            .count();
+        assert!(events.iter().any(|e| matches!(e, Event::Skipped { .. })));
+        let report = banker.report(serde_json::Value::Null);
+        let batched: usize = report.batches.iter().map(|b| b.payments).sum();
+        assert_eq!(submitted, batched);
+        let summary = format!("Sent {} txns in", submitted);
+        assert!(events.iter().any(|e| matches!(
+            e,
+            Event::Message(m) if m.starts_with(&summary)
+        )));
+    }
+
+    #[test]
+    fn test_amounts_stay_within_known_balance() {
+        // Each wallet pays five times a block, but its 10 bones run out
+        // after 4, 4 and the last 2, or after one payment and its fee
+        for &fee in &[0, 3] {
+            let dir = tempfile::tempdir().unwrap();
+            let recorder = Arc::new(RecordingObserver::new());
+            let (builder, backend) = sim_builder(dir.path(), 3, 10, fast_sim_config(fee));
+            let banker = builder
+                .observer(recorder.clone())
+                .build_with(backend)
+                .unwrap();
+            let traffic = Traffic {
+                amount: Amount::Fixed(4),
+                seed: Some(1),
+                ..Traffic::default()
+            };
+            let profile = LoadProfile::new(Shape::Constant(15), Some(Span::Blocks(3))).unwrap();
+            banker
+                .pay_forward(&profile, &traffic, &StopConditions::default())
+                .unwrap();
+
+            let events = recorder.events();
+            let count = |f: fn(&Event) -> bool| events.iter().filter(|e| f(e)).count();
+            assert!(count(|e| matches!(e, Event::Skipped { .. })) > 0);
+            assert!(count(|e| matches!(e, Event::PaymentConfirmed { .. })) > 0);
+            assert_eq!(0, count(|e| matches!(e, Event::PaymentFailed { .. })));
+            assert_eq!(0, count(|e| matches!(e, Event::PaymentRejected { .. })));
+
+            // Batches count what was submitted, not what was skipped
+            let finished: usize = events
+                .iter()
+                .map(|e| match e {
+                    Event::BatchFinished { payments, .. } => *payments,
+                    _ => 0,
+                })
+                .sum();
+            let report = banker.report(serde_json::Value::Null);
+            let batched: usize = report.batches.iter().map(|b| b.payments).sum();
+            let submitted = count(|e| matches!(e, Event::PaymentSubmitted { .. }));
+            assert_eq!(submitted, finished);
+            assert_eq!(submitted, batched);
+        }
+    }
+
+    #[test]
+    fn test_traffic_needs_two_wallets() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Arc::new(RecordingObserver::new());
+        let banker = funded_sim_banker(dir.path(), 1, 1_000, recorder);
+        let profile = LoadProfile::new(Shape::Constant(5), None).unwrap();
+        let stop = StopConditions::default();
+        let err = banker
+            .pay_forward(&profile, &Traffic::default(), &stop)
+            .unwrap_err();
+        assert!(matches!(err, BankError::Config(_)));
+
+        let rate = Rate::new(5.0, None).unwrap();
+        let err = banker
+            .pay_at_rate(&rate, None, &Traffic::default(), &stop)
+            .unwrap_err();
+        assert!(matches!(err, BankError::Config(_)));
+    }
+
+    #[test]
+    fn test_stop_conditions_end_an_endless_profile() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Arc::new(RecordingObserver::new());
+        let banker = funded_sim_banker(dir.path(), 2, 1_000, recorder.clone());
+        let profile = LoadProfile::new(Shape::Constant(5), None).unwrap();
+        let stop = StopConditions {
+            max_txns: Some(8),
+            ..StopConditions::default()
+        };
+        banker
+            .pay_forward(&profile, &Traffic::default(), &stop)
+            .unwrap();
+
+        // Checked between batches, so the second batch is sent in full
+        let events = recorder.events();
+        let submitted = events
+            .iter()
+            .filter(|e| matches!(e, Event::PaymentSubmitted { .. }))
+            .count();
+        assert_eq!(10, submitted);
+        assert!(events.iter().any(|e| matches!(
+            e,
+            Event::Message(m) if m == "Stopping, sent 10 of at most 8 txns."
+        )));
+
+        let stop = StopConditions {
+            max_spend: Some(3),
+            ..StopConditions::default()
+        };
+        banker.fan_out(&stop).unwrap();
+        assert!(recorder.events().iter().any(|e| matches!(
+            e,
+            Event::Message(m) if m.starts_with("Stopping, spent")
+        )));
+    }
+
+    /// A banker on a fast simulated chain over `count` new wallets
+    /// that each start out holding `bones`.
+    fn funded_sim_banker(
+        dir: &Path,
+        count: usize,
+        bones: u64,
+        observer: Arc<RecordingObserver>,
+    ) -> Banker<SimBackend> {
+        let (builder, backend) = funded_sim_builder(dir, count, bones);
+        builder.observer(observer).build_with(backend).unwrap()
+    }
+
+    /// The builder and simulated chain behind `funded_sim_banker`.
+    fn funded_sim_builder(dir: &Path, count: usize, bones: u64) -> (BankerBuilder, SimBackend) {
+        sim_builder(dir, count, bones, fast_sim_config(0))
+    }
+
+    /// `funded_sim_builder` over a chain set up with `config`.
+    fn sim_builder(
+        dir: &Path,
+        count: usize,
+        bones: u64,
+        config: SimConfig,
+    ) -> (BankerBuilder, SimBackend) {
+        let genesis = create_key_files(dir, count)
+            .into_iter()
+            .map(|address| (address, bones))
+            .collect();
+        (test_builder(dir), SimBackend::new(config, genesis))
+    }
+
+    /// Creates `count` wallets in `dir` and returns their addresses in
+    /// the order the banker loads them.
+    fn create_key_files(dir: &Path, count: usize) -> Vec<String> {
+        for i in 1..=count {
+            let path = dir.join(format!("wallet_{:05}.key", i));
+            cmd_create::cmd_basic("test-password", 2, path, false, None).unwrap();
+        }
+        let (key_paths, _) = Banker::<SimBackend>::get_key_paths(&dir.to_string_lossy()).unwrap();
+        key_paths
+            .iter()
+            .map(|p| KeyFile::load(p, "").unwrap().address)
+            .collect()
+    }
+
+    /// A builder over the wallets in `dir`.
+    fn test_builder(dir: &Path) -> BankerBuilder {
+        BankerBuilder::new()
+            .password(PasswordSource::Plain("test-password".to_string()))
+            .working_dir(&dir.to_string_lossy())
+    }
+
+    /// A chain with 50ms blocks charging `fee` DC a txn.
+    fn fast_sim_config(fee: u64) -> SimConfig {
+        SimConfig {
+            block_time: Duration::from_millis(50),
+            fee,
+            ..SimConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_resumed_seed_does_not_pay_twice() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Arc::new(RecordingObserver::new());
+        let banker = funded_sim_banker(dir.path(), 4, 1_000, recorder.clone());
+        let seeder = banker.wallets()[0].address.clone();
+
+        let results = banker.seed(&seeder, JournalMode::New).unwrap();
+        assert_eq!(1, results.len());
+        banker.wait_for_txns(&banker.tracker.pending()).unwrap();
+        let balances = banker.balances();
+
+        // The journal shows the payment went through, so nothing is sent
+        let results = banker.seed(&seeder, JournalMode::Resume).unwrap();
+        assert!(results.is_empty());
+        assert_eq!(balances, banker.balances());
+        let journal = Journal::resume(&Journal::path(dir.path(), "seed"), "seed", &seeder).unwrap();
+        assert!(journal.payments().iter().all(JournalPayment::is_paid));
+    }
+
+    #[test]
+    fn test_collect_and_seed_pay_fees_exactly() {
+        let dir = tempfile::tempdir().unwrap();
+        let (builder, backend) = sim_builder(dir.path(), 3, 1_000, fast_sim_config(7));
+        let banker = builder.build_with(backend).unwrap();
+        let collector = banker.wallets()[0].address.clone();
+        let balances = || -> Vec<u64> {
+            let wallets = banker.wallets().iter();
+            wallets
+                .map(|w| banker.get_wallet_balance(w).unwrap())
+                .collect()
+        };
+
+        // Both other wallets sweep 993 bones and pay 7 in fees
+        let results = banker.collect(&collector, JournalMode::New).unwrap();
+        assert!(results.iter().all(|r| r.error.is_none()));
+        banker.wait_for_txns(&banker.tracker.pending()).unwrap();
+        assert_eq!(vec![2_986, 0, 0], balances());
+
+        // What is left after the one fee splits three ways
+        banker.seed(&collector, JournalMode::New).unwrap();
+        banker.wait_for_txns(&banker.tracker.pending()).unwrap();
+        assert_eq!(vec![993, 993, 993], balances());
+    }
+
+    #[test]
+    fn test_payments_keep_to_max_payments() {
+        let dir = tempfile::tempdir().unwrap();
+        let (builder, backend) = funded_sim_builder(dir.path(), 4, 1_000);
+        let banker = builder.max_payments(2).build_with(backend).unwrap();
+        assert_eq!(2, banker.max_payments());
+        let seeder = banker.wallets()[0].address.clone();
+
+        // Three payees, two to a payment
+        let plan = banker.plan_seed(&seeder).unwrap();
+        let payees: Vec<usize> = plan.payments().map(|p| p.payees.len()).collect();
+        assert_eq!(vec![2, 1], payees);
+
+        let traffic = Traffic {
+            topology: Topology::FanOut(3),
+            ..Traffic::default()
+        };
+        let profile = LoadProfile::new(Shape::Constant(1), Some(Span::Blocks(1))).unwrap();
+        assert!(banker
+            .pay_forward(&profile, &traffic, &StopConditions::default())
+            .is_err());
+    }
+
+    #[test]
+    fn test_applied_plan_sends_what_was_planned() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Arc::new(RecordingObserver::new());
+        let banker = funded_sim_banker(dir.path(), 4, 1_000, recorder);
+        let seeder = banker.wallets()[0].address.clone();
+
+        let plan = banker.plan_seed(&seeder).unwrap();
+        assert_eq!(1, plan.summary().payments);
+        assert_eq!(750, plan.summary().bones);
+        assert!(plan.overdrawn().is_empty());
+        // Planning sends nothing
+        assert!(banker.tracker.pending().is_empty());
+
+        let results = banker.apply(&plan, false).unwrap();
+        assert!(results.iter().all(|r| r.error.is_none()));
+        banker.wait_for_txns(&banker.tracker.pending()).unwrap();
+        for balance in &plan.balances {
+            let now = banker.get_account_balance(&balance.address).unwrap();
+            assert_eq!(balance.after, now as i64);
+        }
+
+        // The seeder no longer holds what the plan has it send
+        let err = banker.apply(&plan, false).unwrap_err();
+        assert!(matches!(err, BankError::InsufficientFunds { .. }));
+    }
+
+    #[test]
+    fn test_applied_plan_keeps_to_its_fees() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Arc::new(RecordingObserver::new());
+        let (builder, backend) = sim_builder(dir.path(), 3, 1_000, fast_sim_config(2));
+        let banker = builder
+            .observer(recorder.clone())
+            .build_with(backend)
+            .unwrap();
+        let seeder = banker.wallets()[0].address.clone();
+
+        // Fees went up since the plan was made
+        let mut plan = banker.plan_seed(&seeder).unwrap();
+        plan.stages[0][0].fee -= 1;
+        let results = banker.apply(&plan, true).unwrap();
+        assert!(results[0].error.as_deref().unwrap().contains("planned"));
+        assert!(banker.tracker.pending().is_empty());
+        assert_eq!(1_000, banker.get_account_balance(&seeder).unwrap());
+        assert!(recorder
+            .events()
+            .iter()
+            .any(|e| matches!(e, Event::PaymentRejected { .. })));
+    }
+
+    #[test]
+    fn test_resume_settles_txns_cut_off_mid_submit() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Arc::new(RecordingObserver::new());
+        let banker = funded_sim_banker(dir.path(), 3, 1_000, recorder);
+        let wallets = banker.wallets();
+        let seeder = wallets[0].address.clone();
+        let payment = |i: usize| PlannedPayment {
+            payer: seeder.clone(),
+            payees: vec![(wallets[i].address.clone(), 100)],
+        };
+
+        // One txn reached the chain before the crash, the other was
+        // journaled but never sent
+        let path = Journal::path(dir.path(), "seed");
+        let journal = Journal::create(&path, "seed", &seeder).unwrap();
+        let ids = journal.plan(vec![payment(1), payment(2)]).unwrap();
+        let sent = vec![(&wallets[0], payment(1).payees)];
+        banker
+            .submit_journaled(&journal, &ids[..1], sent, &HashMap::new())
+            .unwrap();
+        journal
+            .submitted(ids[1], "never-sent".to_string(), 2)
+            .unwrap();
+        drop(journal);
+        banker.wait_for_txns(&banker.tracker.pending()).unwrap();
+
+        // Only the one that never went out is sent again
+        let results = banker.seed(&seeder, JournalMode::Resume).unwrap();
+        assert_eq!(1, results.len());
+        banker.wait_for_txns(&banker.tracker.pending()).unwrap();
+        let balances: Vec<u64> = (0..3)
+            .map(|i| banker.get_wallet_balance(&wallets[i]).unwrap())
+            .collect();
+        assert_eq!(vec![800, 1_100, 1_100], balances);
+    }
+
+    #[test]
+    fn test_txn_held_back_before_submit_leaves_no_nonce_gap() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Arc::new(RecordingObserver::new());
+        let banker = funded_sim_banker(dir.path(), 2, 1_000, recorder);
+        let wallets = banker.wallets();
+        let payees = vec![(wallets[1].address.clone(), 10)];
+
+        let results = banker.submit_with(vec![(&wallets[0], payees)], |_, _| {
+            Err(BankError::Config("held back".to_string()))
+        });
+        assert!(results[0].is_err());
+
+        // The next txn gets the nonce the held back one was signed with
+        let hash = banker.pay(10, &wallets[0], &wallets[1]).unwrap();
+        banker.wait_for_txns(&[hash]).unwrap();
+        assert_eq!(990, banker.get_wallet_balance(&wallets[0]).unwrap());
+    }
+
+    #[test]
+    fn test_new_run_keeps_an_unfinished_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Arc::new(RecordingObserver::new());
+        let banker = funded_sim_banker(dir.path(), 3, 1_000, recorder);
+        let wallets = banker.wallets();
+        let seeder = wallets[0].address.clone();
+
+        // A finished seed does not stand in the way of the next one
+        banker.seed(&seeder, JournalMode::New).unwrap();
+        banker.seed(&seeder, JournalMode::New).unwrap();
+        banker.wait_for_txns(&banker.tracker.pending()).unwrap();
+
+        // One that crashed before sending its payment does
+        let path = Journal::path(dir.path(), "seed");
+        let journal = Journal::create(&path, "seed", &seeder).unwrap();
+        journal
+            .plan(vec![PlannedPayment {
+                payer: seeder.clone(),
+                payees: vec![(wallets[1].address.clone(), 100)],
+            }])
+            .unwrap();
+        drop(journal);
+        let err = banker.seed(&seeder, JournalMode::New).unwrap_err();
+        assert!(matches!(err, BankError::Config(_)));
+        assert_eq!(1, Journal::reopen(&path).unwrap().unwrap().unfinished());
+
+        // Unless it is set aside
+        banker.seed(&seeder, JournalMode::Fresh).unwrap();
+        let journals = fs::read_dir(dir.path())
+            .unwrap()
+            .filter(|e| {
+                let name = e.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with("seed.journal.")
+            })
+            .count();
+        assert_eq!(2, journals);
+    }
+
+    /// Records events, asking for a shutdown once a payment is sent.
+    struct StopOnSubmit {
+        shutdown: Shutdown,
+        recorder: RecordingObserver,
+    }
+
+    impl Observer for StopOnSubmit {
+        fn on_event(&self, event: &Event) {
+            if let Event::PaymentSubmitted { .. } = event {
+                self.shutdown.request();
+            }
+            self.recorder.on_event(event);
+        }
+    }
+
+    #[test]
+    fn test_shutdown_stops_sending_and_drains() {
+        let dir = tempfile::tempdir().unwrap();
+        let shutdown = Shutdown::new();
+        let observer = Arc::new(StopOnSubmit {
+            shutdown: shutdown.clone(),
+            recorder: RecordingObserver::new(),
+        });
+        let (builder, backend) = funded_sim_builder(dir.path(), 3, 1_000);
+        let banker = builder
+            .observer(observer.clone())
+            .shutdown(shutdown.clone())
+            .build_with(backend)
+            .unwrap();
+
+        // Ask an endless run to stop while it sends its first batch
+        let profile = LoadProfile::new(Shape::Constant(3), None).unwrap();
+        banker
+            .pay_forward(&profile, &Traffic::default(), &StopConditions::default())
+            .unwrap();
+        banker.drain().unwrap();
+
+        assert_eq!(0, banker.tracker.counts().0);
+        let events = observer.recorder.events();
+        let submitted = events
+            .iter()
+            .filter(|e| matches!(e, Event::PaymentSubmitted { .. }))
+            .count();
+        assert_eq!(3, submitted);
+        assert!(events.iter().any(|e| matches!(
+            e,
+            Event::Message(m) if m == "Stopping, shutdown requested."
+        )));
+
+        // Nothing new is scheduled once stopping
+        let seeder = banker.wallets()[0].address.clone();
+        assert!(banker
+            .seed_independent(&seeder, JournalMode::New)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_drain_timeout_counts_from_the_request() {
+        let dir = tempfile::tempdir().unwrap();
+        let shutdown = Shutdown::new();
+        // Blocks hold no txns, so the payment stays pending
+        let config = SimConfig {
+            block_capacity: 0,
+            ..fast_sim_config(0)
+        };
+        let drain_timeout = Duration::from_secs(1);
+        let (builder, backend) = sim_builder(dir.path(), 2, 1_000, config);
+        let banker = builder
+            .shutdown(shutdown.clone())
+            .drain_timeout(drain_timeout)
+            .build_with(backend)
+            .unwrap();
+        let wallets = banker.wallets();
+        banker.pay(10, &wallets[0], &wallets[1]).unwrap();
+
+        // The first wait uses up the timeout
+        shutdown.request();
+        banker.wait_for_txns(&banker.tracker.pending()).unwrap();
+        let requested = shutdown.requested_at().unwrap();
+        assert!(requested.elapsed() >= drain_timeout);
+
+        // So neither the drain nor a wait for the next block starts
+        // a timeout of its own
+        let started = Instant::now();
+        banker.drain().unwrap();
+        banker.wait_unless_stopping(10);
+        assert!(started.elapsed() < drain_timeout);
+        assert_eq!(1, banker.tracker.counts().0);
+    }
+
+    #[test]
+    fn test_bad_key_file_is_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet_00001.key");
+        cmd_create::cmd_basic("test-password", 2, path, false, None).unwrap();
+        fs::write(dir.path().join("wallet_00002.key"), b"not a wallet").unwrap();
+
+        let working_dir = dir.path().to_string_lossy().to_string();
+        let backend = SimBackend::new(SimConfig::default(), vec![]);
+        let banker = BankerBuilder::new()
+            .password(PasswordSource::Plain("test-password".to_string()))
+            .working_dir(&working_dir)
+            .build_with(backend)
+            .unwrap();
+
+        assert_eq!(1, banker.wallets().len());
+        let balances = banker.balances();
+        assert_eq!(Some(0), balances[0].balance);
+        assert!(balances[1].error.is_some());
+        assert!(matches!(
+            banker.wallet_from_address("13Ad3bq7UDGYUG7xkKGAQX3vJkWQ3B5ERR3FGhhvqnEktnRNtw2"),
+            Err(BankError::UnknownAddress(_))
+        ));
+    }
+
+    #[test]
+    fn test_password_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("password");
+        fs::write(&path, "secret\n").unwrap();
+
+        assert_eq!("secret", *PasswordSource::File(path).read().unwrap());
+        assert!(matches!(
+            PasswordSource::Env("HELIUM_LOAD_UNSET_PASSWORD".to_string()).read(),
+            Err(BankError::Config(_))
+        ));
+    }
 }
@@ -1,4 +1,12 @@
+use std::{str::FromStr, time::Duration};
+
 use clap::Clap;
+use helium_load::amount::Amount;
+use helium_load::journal::JournalMode;
+use helium_load::profile::{Shape, Span};
+use helium_load::topology::Topology;
+use helium_load::{StopConditions, Traffic};
+use serde::Serialize;
 
 /// This tool assists users in managing a "bank" of wallets. It
 /// is very useful for load testing and bulk processing.
@@ -9,20 +17,49 @@ pub struct Opts {
     #[clap(short = "d", long = "dir", default_value = ".")]
     pub working_dir: String,
     /// The number of threads to use while processing data.
-    /// NOTE: some processes can't make that much use of more than
-    /// 1 in order not to have nonce conflicts. Defaults to use same
-    /// number of threads as logical CPU cores.
+    /// Nonces are tracked locally, so payments from the same wallet
+    /// can be sent concurrently. Defaults to use same number of
+    /// threads as logical CPU cores.
     #[clap(short = "t", long = "threads", default_value = "0")]
     pub threads: usize,
+    /// The most requests to keep in flight against the Helium API at
+    /// once, independent of the number of threads.
+    #[clap(long = "concurrency", default_value = "256")]
+    pub concurrency: usize,
+    /// Seconds to wait for pending txns to clear after SIGINT or
+    /// SIGTERM before writing the report and exiting. A second
+    /// signal exits right away.
+    #[clap(long = "drain-timeout", default_value = "60")]
+    pub drain_timeout: u64,
+    /// The most payees to put in one payment. Defaults to the chain's
+    /// `max_payments` variable.
+    #[clap(long = "max-payments")]
+    pub max_payments: Option<usize>,
+    /// The chain to run against: `http` uses the Helium API at
+    /// API_URL, `sim` an in-process simulated ledger.
+    #[clap(long = "backend", default_value = "http", possible_values = &["http", "sim"])]
+    pub backend: String,
+    /// Where to write the JSON run report. Defaults to a timestamped
+    /// file in the working directory.
+    #[clap(long = "report")]
+    pub report: Option<String>,
+    #[clap(flatten)]
+    pub sim: SimOpts,
     #[clap(subcommand)]
     pub subcmd: SubCommand,
 }
 
-#[derive(Clap)]
+#[derive(Clap, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum SubCommand {
+    /// Sends exactly the payments of a plan written with `--plan`,
+    /// after showing a summary and asking for confirmation.
+    #[clap(name = "apply")]
+    Apply(ApplyOpts),
+
     /// Prints the balance of all wallets
     #[clap(name = "balances")]
-    Balances,
+    Balances(BalancesOpts),
 
     /// Collect all wallet balances into a single wallet
     #[clap(name = "collect")]
@@ -34,7 +71,7 @@ pub enum SubCommand {
 
     /// Distributes each wallet's balance amongst all other wallets
     #[clap(name = "fanout")]
-    Fanout,
+    Fanout(FanoutOpts),
 
     /// Prints the wallet with the highest balance
     #[clap(name = "max-balance")]
@@ -50,36 +87,380 @@ pub enum SubCommand {
     #[clap(name = "seed-independent")]
     SeedIndependent(SeedOpts),
 
+    /// Runs a multi-phase scenario described in a TOML file.
+    #[clap(name = "run")]
+    Run(RunOpts),
+
+    /// Serves a mock Helium API backed by the simulated ledger.
+    /// Configure the ledger with the `--sim-*` options.
+    #[clap(name = "serve-mock")]
+    ServeMock(ServeMockOpts),
+
     /// Creates a sustained volume of txns/block.
     #[clap(name = "sustained")]
     Sustained(SustainedOpts),
 }
 
-/// A subcommand for controlling wallet creation
+impl SubCommand {
+    /// Whether this only works out the payments it would send.
+    pub fn planning(&self) -> bool {
+        match self {
+            SubCommand::Collect(opts) => opts.plan.planning(),
+            SubCommand::Fanout(opts) => opts.plan.planning(),
+            SubCommand::Seed(opts) | SubCommand::SeedIndependent(opts) => opts.plan.planning(),
+            _ => false,
+        }
+    }
+}
+
+/// Settings for the simulated ledger used by `--backend sim`
 #[derive(Clap)]
+pub struct SimOpts {
+    /// Milliseconds between simulated blocks.
+    #[clap(long = "sim-block-ms", default_value = "1000")]
+    pub block_ms: u64,
+    /// Maximum txns included in each simulated block.
+    #[clap(long = "sim-block-capacity", default_value = "1000")]
+    pub block_capacity: usize,
+    /// Fee in bones charged for every simulated txn.
+    #[clap(long = "sim-fee", default_value = "0")]
+    pub fee: u64,
+    /// Most payees a simulated txn may have.
+    #[clap(
+        name = "sim-max-payments",
+        long = "sim-max-payments",
+        default_value = "50"
+    )]
+    pub max_payments: usize,
+    /// Starting balance of an address, as ADDRESS=BONES. May be repeated.
+    #[clap(long = "sim-genesis", parse(try_from_str = parse_genesis))]
+    pub genesis: Vec<(String, u64)>,
+}
+
+fn parse_genesis(s: &str) -> Result<(String, u64), String> {
+    let mut parts = s.splitn(2, '=');
+    match (parts.next(), parts.next()) {
+        (Some(address), Some(bones)) => bones
+            .parse()
+            .map(|bones| (address.to_string(), bones))
+            .map_err(|e| format!("invalid bones in {}: {}", s, e)),
+        _ => Err(format!("expected ADDRESS=BONES, got {}", s)),
+    }
+}
+
+/// A subcommand for printing balances
+#[derive(Clap, Serialize)]
+pub struct BalancesOpts {
+    /// The output format, one of table, json or csv.
+    #[clap(long = "format", default_value = "table", possible_values = &["table", "json", "csv"])]
+    pub format: Format,
+}
+
+/// How balances are printed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Table,
+    Json,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            _ => Err(format!("unknown format {}", s)),
+        }
+    }
+}
+
+/// A subcommand for controlling wallet creation
+#[derive(Clap, Serialize)]
 pub struct CreateOpts {
     /// The number of wallets to create
     pub count: usize,
 }
 
 /// A subcommand for collecting wallet balances
-#[derive(Clap)]
+#[derive(Clap, Serialize)]
 pub struct CollectOpts {
     /// The address to collect all balances into
     pub address: String,
+    /// Carries on from the journal of an interrupted collect, only
+    /// sending the payments that did not go through.
+    #[clap(long = "resume")]
+    pub resume: bool,
+    /// Sets aside the journal of an interrupted collect and starts over.
+    #[clap(long = "fresh", conflicts_with = "resume")]
+    pub fresh: bool,
+    #[clap(flatten)]
+    pub plan: PlanOpts,
+}
+
+impl CollectOpts {
+    pub fn journal_mode(&self) -> JournalMode {
+        journal_mode(self.resume, self.fresh)
+    }
 }
 
 /// A subcommand for seeding wallets
-#[derive(Clap)]
+#[derive(Clap, Serialize)]
 pub struct SeedOpts {
     /// Seeds all the keys in working director with equal
     /// division of balance, from the address provided.
     pub address: String,
+    /// Carries on from the journal of an interrupted seed, without
+    /// paying any wallet twice.
+    #[clap(long = "resume")]
+    pub resume: bool,
+    /// Sets aside the journal of an interrupted seed and starts over.
+    #[clap(long = "fresh", conflicts_with = "resume")]
+    pub fresh: bool,
+    #[clap(flatten)]
+    pub plan: PlanOpts,
+}
+
+impl SeedOpts {
+    pub fn journal_mode(&self) -> JournalMode {
+        journal_mode(self.resume, self.fresh)
+    }
+}
+
+fn journal_mode(resume: bool, fresh: bool) -> JournalMode {
+    match (resume, fresh) {
+        (true, _) => JournalMode::Resume,
+        (false, true) => JournalMode::Fresh,
+        (false, false) => JournalMode::New,
+    }
+}
+
+/// A subcommand for fanning out balances
+#[derive(Clap, Serialize)]
+pub struct FanoutOpts {
+    #[clap(flatten)]
+    pub stop: StopOpts,
+    #[clap(flatten)]
+    pub plan: PlanOpts,
+}
+
+/// Works out the payments of a spending command without sending them
+#[derive(Clap, Serialize)]
+pub struct PlanOpts {
+    /// Writes the payments to this file for review instead of sending
+    /// them. Send them later with `apply`.
+    #[clap(long = "plan")]
+    pub plan: Option<String>,
+    /// Prints a summary of the payments instead of sending them.
+    #[clap(long = "dry-run")]
+    pub dry_run: bool,
+}
+
+impl PlanOpts {
+    pub fn planning(&self) -> bool {
+        self.plan.is_some() || self.dry_run
+    }
+}
+
+/// A subcommand for applying a plan
+#[derive(Clap, Serialize)]
+pub struct ApplyOpts {
+    /// Path to the plan file.
+    pub plan: String,
+    /// Applies the plan without asking for confirmation.
+    #[clap(long = "yes")]
+    pub yes: bool,
+    /// Sets aside the journal of an interrupted apply and starts over.
+    #[clap(long = "fresh")]
+    pub fresh: bool,
+}
+
+/// When a command that otherwise runs forever should finish, checked
+/// between batches. The first one reached stops the run.
+#[derive(Clap, Serialize)]
+pub struct StopOpts {
+    /// Stops after this much wall time, such as 90s, 30m or 8h.
+    #[clap(long = "duration", parse(try_from_str = parse_wall_time))]
+    pub duration: Option<Duration>,
+    /// Stops after this many blocks.
+    #[clap(long = "blocks")]
+    pub blocks: Option<u64>,
+    /// Stops once this many txns have been accepted.
+    #[clap(long = "max-txns")]
+    pub max_txns: Option<u64>,
+    /// Stops once the chain reaches this height.
+    #[clap(long = "until-height")]
+    pub until_height: Option<u64>,
+    /// Stops once this many bones, fees included, have been spent.
+    #[clap(long = "max-spend")]
+    pub max_spend: Option<u64>,
+}
+
+impl StopOpts {
+    pub fn conditions(&self) -> StopConditions {
+        StopConditions {
+            duration: self.duration,
+            blocks: self.blocks,
+            max_txns: self.max_txns,
+            until_height: self.until_height,
+            max_spend: self.max_spend,
+        }
+    }
+}
+
+fn parse_wall_time(s: &str) -> Result<Duration, String> {
+    match s.parse()? {
+        Span::Secs(secs) => Ok(Duration::from_secs(secs)),
+        Span::Blocks(_) => Err(format!(
+            "expected wall time in {}, use --blocks for blocks",
+            s
+        )),
+    }
+}
+
+/// A subcommand for running scenario files
+#[derive(Clap, Serialize)]
+pub struct RunOpts {
+    /// Path to the scenario file.
+    pub scenario: String,
+}
+
+/// A subcommand for serving a mock Helium API
+#[derive(Clap, Serialize)]
+pub struct ServeMockOpts {
+    /// The address to listen on.
+    #[clap(long = "listen", default_value = "127.0.0.1:4000")]
+    pub listen: String,
+    /// Milliseconds of latency added to every response.
+    #[clap(long = "latency-ms", default_value = "0")]
+    pub latency_ms: u64,
+    /// Fraction (0.0-1.0) of requests answered with a server error.
+    #[clap(long = "error-rate", default_value = "0")]
+    pub error_rate: f64,
+    /// Fraction (0.0-1.0) of submitted txns acknowledged but dropped.
+    #[clap(long = "drop-rate", default_value = "0")]
+    pub drop_rate: f64,
+    /// The number of threads answering requests.
+    #[clap(long = "workers", default_value = "16")]
+    pub workers: usize,
 }
 
 /// A subcommand for creating sustained txn volume
-#[derive(Clap)]
+#[derive(Clap, Serialize)]
 pub struct SustainedOpts {
-    /// The total txns to sustain per block.
-    pub count: usize,
+    /// The total txns to sustain per block. Wallets send more than
+    /// one txn per block when this exceeds the number of wallets.
+    /// Required unless `--profile` or `--tps` is given.
+    pub count: Option<usize>,
+    /// Varies txns per block over time instead of a fixed count. One of
+    /// constant:N, ramp:FROM:TO, step:START:STEP:EVERY,
+    /// spike:BASE:PEAK:EVERY:LENGTH, sine:BASE:AMPLITUDE:PERIOD or
+    /// soak:N, where spans are blocks (10b) or wall time (90s, 5m, 2h).
+    #[clap(long = "profile")]
+    pub profile: Option<Shape>,
+    /// How long to run the profile for, in blocks (100b) or wall time
+    /// (30m). Runs forever if not given, except ramps which need one.
+    #[clap(long = "profile-duration")]
+    pub profile_duration: Option<Span>,
+    /// Sends this many txns per second regardless of block boundaries,
+    /// instead of a count or profile per block. Reports when
+    /// submission cannot keep up with the rate.
+    #[clap(long = "tps", conflicts_with_all = &["count", "profile"])]
+    pub tps: Option<f64>,
+    /// The most txns sent at once to catch up after a slow submission
+    /// under `--tps`. Defaults to one second's worth.
+    #[clap(long = "burst", requires = "tps")]
+    pub burst: Option<usize>,
+    /// Who pays whom: ring, random, star, mesh, zipf:EXPONENT (a few
+    /// wallets receive most payments) or fanout:WIDTH (multi-payee
+    /// txns to WIDTH wallets, at most `--max-payments`).
+    #[clap(long = "topology", default_value = "ring")]
+    pub topology: Topology,
+    /// Bones paid to each payee: fixed:BONES, uniform:MIN:MAX,
+    /// lognormal:MEDIAN:SIGMA or percent:P of the payer's balance.
+    /// Payments are kept within what each payer is known to hold.
+    #[clap(long = "amount", default_value = "fixed:1")]
+    pub amount: Amount,
+    /// Seeds payee choice and amounts so a run can be reproduced.
+    /// A random seed is used, and printed, if not given.
+    #[clap(long = "seed")]
+    pub seed: Option<u64>,
+    #[clap(flatten)]
+    pub stop: StopOpts,
+}
+
+impl SustainedOpts {
+    pub fn traffic(&self) -> Traffic {
+        Traffic {
+            topology: self.topology.clone(),
+            amount: self.amount.clone(),
+            seed: self.seed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_payments_is_its_own_arg() {
+        let opts = Opts::try_parse_from(&["helium-load", "balances"]).unwrap();
+        assert_eq!(None, opts.max_payments);
+        assert_eq!(50, opts.sim.max_payments);
+
+        let opts = Opts::try_parse_from(&[
+            "helium-load",
+            "--max-payments",
+            "10",
+            "--sim-max-payments",
+            "3",
+            "balances",
+        ])
+        .unwrap();
+        assert_eq!(Some(10), opts.max_payments);
+        assert_eq!(3, opts.sim.max_payments);
+    }
+
+    #[test]
+    fn test_profile_duration_and_stop_duration_are_apart() {
+        let opts = Opts::try_parse_from(&[
+            "helium-load",
+            "sustained",
+            "--profile",
+            "ramp:1:10",
+            "--profile-duration",
+            "10b",
+            "--duration",
+            "5m",
+        ])
+        .unwrap();
+        match opts.subcmd {
+            SubCommand::Sustained(opts) => {
+                assert_eq!(Some(Span::Blocks(10)), opts.profile_duration);
+                assert_eq!(Some(Duration::from_secs(300)), opts.stop.duration);
+            }
+            _ => panic!("expected sustained"),
+        }
+    }
+
+    #[test]
+    fn test_seed_journal_mode() {
+        let mode = |args: &[&str]| {
+            let base = ["helium-load", "seed", "addr"];
+            let args: Vec<&str> = base.iter().chain(args).cloned().collect();
+            match Opts::try_parse_from(&args).map(|opts| opts.subcmd) {
+                Ok(SubCommand::Seed(opts)) => Ok(opts.journal_mode()),
+                Ok(_) => panic!("expected seed"),
+                Err(_) => Err(()),
+            }
+        };
+        assert_eq!(Ok(JournalMode::New), mode(&[]));
+        assert_eq!(Ok(JournalMode::Resume), mode(&["--resume"]));
+        assert_eq!(Ok(JournalMode::Fresh), mode(&["--fresh"]));
+        assert!(mode(&["--resume", "--fresh"]).is_err());
+    }
 }
@@ -0,0 +1,124 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+pub type Result<T> = std::result::Result<T, BankError>;
+
+/// Everything that can go wrong while banking. Each kind has its own
+/// exit code so scripts driving the CLI can tell them apart.
+#[derive(Debug)]
+pub enum BankError {
+    /// A key file or directory could not be read or written.
+    Io(PathBuf, io::Error),
+    /// A key file could be read but does not hold a wallet.
+    WalletDecode(PathBuf, String),
+    /// The password does not decrypt the key file.
+    WrongPassword(PathBuf),
+    /// The API could not be reached or answered with an error.
+    Api(String),
+    /// The chain refused a txn.
+    Rejected(String),
+    /// No key file in the working directory holds the address.
+    UnknownAddress(String),
+    /// The string is not a valid b58 address.
+    InvalidAddress(String),
+    InsufficientFunds {
+        address: String,
+        balance: u64,
+        needed: u64,
+    },
+    /// Waiting on the chain took longer than allowed.
+    Timeout(String),
+    /// Missing or invalid settings, such as an unset env var.
+    Config(String),
+}
+
+impl BankError {
+    /// Wraps an error from the API client or HTTP stack.
+    pub fn api(e: impl fmt::Display) -> Self {
+        BankError::Api(e.to_string())
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            BankError::Config(_) => 2,
+            BankError::Io(..) => 3,
+            BankError::WalletDecode(..) => 4,
+            BankError::WrongPassword(_) => 5,
+            BankError::Api(_) => 6,
+            BankError::Rejected(_) => 7,
+            BankError::UnknownAddress(_) => 8,
+            BankError::InvalidAddress(_) => 9,
+            BankError::InsufficientFunds { .. } => 10,
+            BankError::Timeout(_) => 11,
+        }
+    }
+}
+
+impl fmt::Display for BankError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BankError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            BankError::WalletDecode(path, e) => {
+                write!(f, "{} is not a wallet: {}", path.display(), e)
+            }
+            BankError::WrongPassword(path) => {
+                write!(f, "wrong password for {}", path.display())
+            }
+            BankError::Api(e) => write!(f, "API error: {}", e),
+            BankError::Rejected(e) => write!(f, "{}", e),
+            BankError::UnknownAddress(address) => {
+                write!(f, "no wallet for address {}", address)
+            }
+            BankError::InvalidAddress(address) => write!(f, "invalid address {}", address),
+            BankError::InsufficientFunds {
+                address,
+                balance,
+                needed,
+            } => write!(
+                f,
+                "insufficient funds: {} holds {} bones, needs {}",
+                address, balance, needed
+            ),
+            BankError::Timeout(e) => write!(f, "timed out: {}", e),
+            BankError::Config(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for BankError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BankError::Io(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let errors = vec![
+            BankError::Io(PathBuf::new(), io::Error::from(io::ErrorKind::NotFound)),
+            BankError::WalletDecode(PathBuf::new(), String::new()),
+            BankError::WrongPassword(PathBuf::new()),
+            BankError::Api(String::new()),
+            BankError::Rejected(String::new()),
+            BankError::UnknownAddress(String::new()),
+            BankError::InvalidAddress(String::new()),
+            BankError::InsufficientFunds {
+                address: String::new(),
+                balance: 0,
+                needed: 1,
+            },
+            BankError::Timeout(String::new()),
+            BankError::Config(String::new()),
+        ];
+        let mut codes: Vec<i32> = errors.iter().map(|e| e.exit_code()).collect();
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(errors.len(), codes.len());
+        assert!(!codes.contains(&0) && !codes.contains(&1));
+    }
+}
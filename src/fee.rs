@@ -0,0 +1,95 @@
+use prost::Message;
+
+use crate::error::Result;
+use crate::txn::PaymentTxn;
+
+/// Bones in one HNT.
+const BONES_PER_HNT: u128 = 100_000_000;
+/// Oracle prices are in 1/100_000_000 USD.
+const ORACLE_PRICE_SCALE: u128 = 100_000_000;
+/// DC in one USD.
+const DC_PER_USD: u128 = 100_000;
+
+/// The chain variables and oracle price that set what a txn costs.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FeeVars {
+    /// DC charged for every `dc_payload_size` bytes of a txn, or any
+    /// part of them. Fees are off when it is 0.
+    pub txn_fee_multiplier: u64,
+    pub dc_payload_size: u64,
+    /// Price of an HNT in 1/100_000_000 USD.
+    pub oracle_price: u64,
+}
+
+impl FeeVars {
+    /// The fee, in DC, the chain expects in `txn`'s fee field. It is
+    /// priced on the txn's encoding with no fee and a blank signature,
+    /// so it does not depend on either.
+    pub fn dc(&self, txn: &PaymentTxn) -> Result<u64> {
+        if self.txn_fee_multiplier == 0 {
+            return Ok(0);
+        }
+        let size = payload_len(txn)? as u64;
+        let payload_size = self.dc_payload_size.max(1);
+        let payloads = ((size + payload_size - 1) / payload_size).max(1);
+        Ok(payloads * self.txn_fee_multiplier)
+    }
+
+    /// The bones of HNT burned to pay `dc` from a wallet holding no
+    /// DC, at the oracle price and rounded up as the chain does.
+    pub fn dc_to_bones(&self, dc: u64) -> u64 {
+        if dc == 0 {
+            return 0;
+        }
+        let price = u128::from(self.oracle_price.max(1));
+        let scaled = u128::from(dc) * BONES_PER_HNT * ORACLE_PRICE_SCALE / DC_PER_USD;
+        ((scaled + price - 1) / price) as u64
+    }
+}
+
+/// Length of the payment_v2 encoding fees are charged on.
+fn payload_len(txn: &PaymentTxn) -> Result<usize> {
+    let mut txn = txn.to_proto()?;
+    txn.fee = 0;
+    txn.signature = vec![0; 64];
+    Ok(txn.encoded_len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDR: &str = "13Ad3bq7UDGYUG7xkKGAQX3vJkWQ3B5ERR3FGhhvqnEktnRNtw2";
+
+    fn vars(txn_fee_multiplier: u64) -> FeeVars {
+        FeeVars {
+            txn_fee_multiplier,
+            dc_payload_size: 24,
+            oracle_price: 150_000_000,
+        }
+    }
+
+    #[test]
+    fn test_fee_grows_with_payees_only() {
+        let one = PaymentTxn::new(ADDR, vec![(ADDR.to_string(), 10)], 1);
+        let fee = vars(5000).dc(&one).unwrap();
+        assert_eq!(0, fee % 5000);
+        assert!(fee > 0);
+
+        let mut signed = one.clone();
+        signed.fee = fee;
+        signed.signature = vec![7; 64];
+        assert_eq!(fee, vars(5000).dc(&signed).unwrap());
+
+        let two = PaymentTxn::new(ADDR, vec![(ADDR.to_string(), 10); 2], 1);
+        assert!(vars(5000).dc(&two).unwrap() > fee);
+        assert_eq!(0, vars(0).dc(&two).unwrap());
+    }
+
+    #[test]
+    fn test_dc_to_bones() {
+        // 35000 DC is $0.35, or 0.2333.. HNT at $1.50
+        assert_eq!(23_333_334, vars(5000).dc_to_bones(35_000));
+        assert_eq!(0, vars(5000).dc_to_bones(0));
+    }
+}
@@ -0,0 +1,378 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{BankError, Result};
+use crate::report::unix_now;
+
+/// What a command does with the journal an earlier run left behind.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum JournalMode {
+    /// Starts a new journal, refusing to while the earlier one still
+    /// has payments that are not known to be paid.
+    New,
+    /// Carries on from the earlier journal.
+    Resume,
+    /// Starts a new journal, setting the earlier one aside.
+    Fresh,
+}
+
+/// One line of a journal. Entries are only ever appended, and each is
+/// flushed to disk before the step it records goes ahead.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "entry", rename_all = "snake_case")]
+pub enum Entry {
+    /// `command` started paying from, or into, `address`.
+    Started { command: String, address: String },
+    /// Payments about to be sent, numbered on from `first`.
+    Planned {
+        first: usize,
+        payments: Vec<PlannedPayment>,
+    },
+    /// Payment `id` is being submitted as `hash`, using `nonce`. It is
+    /// written before the txn is sent, so whether a txn cut off by a
+    /// crash went through can be told from its nonce.
+    Submitted { id: usize, hash: String, nonce: u64 },
+    /// Payment `id` was refused when submitted.
+    Rejected { id: usize, error: String },
+    /// Payment `id` cleared, or failed.
+    Resolved { id: usize, cleared: bool },
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PlannedPayment {
+    pub payer: String,
+    pub payees: Vec<(String, u64)>,
+}
+
+/// What the journal knows about one planned payment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JournalPayment {
+    pub payer: String,
+    pub payees: Vec<(String, u64)>,
+    /// Hash and nonce of the txn, once submitted.
+    pub submitted: Option<(String, u64)>,
+    /// Whether the payment went through, once known. A rejected or
+    /// failed payment can be sent again.
+    pub cleared: Option<bool>,
+}
+
+impl JournalPayment {
+    pub fn is_paid(&self) -> bool {
+        self.cleared == Some(true)
+    }
+
+    /// Submitted, but not yet known to have cleared or failed.
+    pub fn is_unsettled(&self) -> bool {
+        self.submitted.is_some() && self.cleared.is_none()
+    }
+}
+
+/// An append-only record of the payments a command planned, sent and
+/// saw settle, kept in the working directory so an interrupted run
+/// can pick up where it left off without paying anyone twice.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    command: String,
+    address: String,
+    file: Mutex<File>,
+    payments: Mutex<Vec<JournalPayment>>,
+}
+
+impl Journal {
+    /// Where the journal of `command` lives in `dir`.
+    pub fn path(dir: &Path, command: &str) -> PathBuf {
+        dir.join(format!("{}.journal.jsonl", command))
+    }
+
+    /// Starts a new journal at `path`, replacing any earlier one.
+    pub fn create(path: &Path, command: &str, address: &str) -> Result<Self> {
+        let file = File::create(path).map_err(|e| BankError::Io(path.to_path_buf(), e))?;
+        let journal = Self {
+            path: path.to_path_buf(),
+            command: command.to_string(),
+            address: address.to_string(),
+            file: Mutex::new(file),
+            payments: Mutex::new(vec![]),
+        };
+        journal.append(Entry::Started {
+            command: command.to_string(),
+            address: address.to_string(),
+        })?;
+        Ok(journal)
+    }
+
+    /// Reopens the journal at `path` to carry on appending to it,
+    /// replaying what it holds. It must have been started by the same
+    /// command and address. A missing journal is started afresh.
+    pub fn resume(path: &Path, command: &str, address: &str) -> Result<Self> {
+        match Self::reopen(path)? {
+            None => Self::create(path, command, address),
+            Some(j) if j.command != command || j.address != address => Err(invalid(
+                path,
+                format!("journal is for {} with {}", j.command, j.address),
+            )),
+            Some(journal) => Ok(journal),
+        }
+    }
+
+    /// Reopens the journal at `path` whatever command started it. None
+    /// when there is no journal, or nothing whole in it.
+    pub fn reopen(path: &Path) -> Result<Option<Self>> {
+        let text = match read(path)? {
+            Some(text) => text,
+            None => return Ok(None),
+        };
+        let (lines, payments) = replay(path, &text)?;
+        let (command, address) = match lines.first().map(|line| serde_json::from_str(line)) {
+            None => return Ok(None),
+            Some(Ok(Entry::Started { command, address })) => (command, address),
+            _ => {
+                let why = "journal does not start with a command".to_string();
+                return Err(invalid(path, why));
+            }
+        };
+        // Drop anything after the last whole entry before appending
+        if !text.ends_with('\n') || lines.len() < text.lines().count() {
+            let whole = lines.join("\n") + "\n";
+            fs::write(path, whole).map_err(|e| BankError::Io(path.to_path_buf(), e))?;
+        }
+
+        let file = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map_err(|e| BankError::Io(path.to_path_buf(), e))?;
+        Ok(Some(Self {
+            path: path.to_path_buf(),
+            command,
+            address,
+            file: Mutex::new(file),
+            payments: Mutex::new(payments),
+        }))
+    }
+
+    /// How many planned payments are not known to be paid.
+    pub fn unfinished(&self) -> usize {
+        let payments = self.payments.lock().unwrap();
+        payments.iter().filter(|p| !p.is_paid()).count()
+    }
+
+    /// Moves the journal at `path` out of the way, returning where to.
+    pub fn set_aside(path: &Path) -> Result<PathBuf> {
+        let aside = path.with_extension(format!("{}.jsonl", unix_now()));
+        fs::rename(path, &aside).map_err(|e| BankError::Io(path.to_path_buf(), e))?;
+        Ok(aside)
+    }
+
+    /// Every payment planned so far, in order, indexed by id.
+    pub fn payments(&self) -> Vec<JournalPayment> {
+        self.payments.lock().unwrap().clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.payments.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Records payments about to be sent, returning their ids.
+    pub fn plan(&self, payments: Vec<PlannedPayment>) -> Result<Vec<usize>> {
+        // Holds the lock so concurrent plans get distinct ids
+        let mut all = self.payments.lock().unwrap();
+        let first = all.len();
+        let ids = (first..first + payments.len()).collect();
+        let entry = Entry::Planned { first, payments };
+        self.write(&entry)?;
+        apply(&mut all, entry);
+        Ok(ids)
+    }
+
+    /// Records that payment `id` is about to be submitted as `hash`,
+    /// signed with `nonce`.
+    pub fn submitted(&self, id: usize, hash: String, nonce: u64) -> Result<()> {
+        self.append(Entry::Submitted { id, hash, nonce })
+    }
+
+    /// Records that the chain refused payment `id`.
+    pub fn rejected(&self, id: usize, error: &BankError) -> Result<()> {
+        self.append(Entry::Rejected {
+            id,
+            error: error.to_string(),
+        })
+    }
+
+    pub fn resolved(&self, id: usize, cleared: bool) -> Result<()> {
+        self.append(Entry::Resolved { id, cleared })
+    }
+
+    fn append(&self, entry: Entry) -> Result<()> {
+        self.write(&entry)?;
+        apply(&mut self.payments.lock().unwrap(), entry);
+        Ok(())
+    }
+
+    fn write(&self, entry: &Entry) -> Result<()> {
+        let mut line = serde_json::to_string(entry).expect("journal entries serialize");
+        line.push('\n');
+        let mut file = self.file.lock().unwrap();
+        file.write_all(line.as_bytes())
+            .and_then(|_| file.sync_data())
+            .map_err(|e| BankError::Io(self.path.clone(), e))
+    }
+}
+
+/// The journal at `path`, or None if there is none.
+fn read(path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(Some(text)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(BankError::Io(path.to_path_buf(), e)),
+    }
+}
+
+fn invalid(path: &Path, why: String) -> BankError {
+    BankError::Config(format!("{}: {}", path.display(), why))
+}
+
+/// Replays the entries of a journal read from `path`, returning its
+/// whole lines and what they tell of the planned payments.
+fn replay<'a>(path: &Path, text: &'a str) -> Result<(Vec<&'a str>, Vec<JournalPayment>)> {
+    let mut lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+    let mut payments = vec![];
+    for (i, line) in lines.clone().into_iter().enumerate() {
+        let entry: Entry = match serde_json::from_str(line) {
+            Ok(entry) => entry,
+            // A crash can leave the last line half written
+            Err(_) if i + 1 == lines.len() => {
+                lines.pop();
+                break;
+            }
+            Err(e) => return Err(invalid(path, format!("line {}: {}", i + 1, e))),
+        };
+        apply(&mut payments, entry);
+    }
+    Ok((lines, payments))
+}
+
+/// Applies `entry` to what is known about the planned payments.
+fn apply(payments: &mut Vec<JournalPayment>, entry: Entry) {
+    match entry {
+        Entry::Started { .. } => {}
+        Entry::Planned {
+            first,
+            payments: planned,
+        } => {
+            payments.truncate(first);
+            payments.extend(planned.into_iter().map(|p| JournalPayment {
+                payer: p.payer,
+                payees: p.payees,
+                submitted: None,
+                cleared: None,
+            }));
+        }
+        Entry::Submitted { id, hash, nonce } => {
+            if let Some(payment) = payments.get_mut(id) {
+                payment.submitted = Some((hash, nonce));
+                payment.cleared = None;
+            }
+        }
+        Entry::Rejected { id, .. } => {
+            if let Some(payment) = payments.get_mut(id) {
+                payment.submitted = None;
+                payment.cleared = Some(false);
+            }
+        }
+        Entry::Resolved { id, cleared } => {
+            if let Some(payment) = payments.get_mut(id) {
+                payment.cleared = Some(cleared);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn planned(payer: &str, payee: &str) -> PlannedPayment {
+        PlannedPayment {
+            payer: payer.to_string(),
+            payees: vec![(payee.to_string(), 5)],
+        }
+    }
+
+    #[test]
+    fn test_resume_replays_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = Journal::path(dir.path(), "seed");
+
+        let journal = Journal::create(&path, "seed", "seeder").unwrap();
+        let ids = journal
+            .plan(vec![
+                planned("a", "b"),
+                planned("a", "c"),
+                planned("a", "d"),
+            ])
+            .unwrap();
+        assert_eq!(vec![0, 1, 2], ids);
+        journal.submitted(0, "h0".to_string(), 1).unwrap();
+        journal.submitted(1, "h1".to_string(), 2).unwrap();
+        journal.submitted(2, "h2".to_string(), 3).unwrap();
+        journal
+            .rejected(2, &BankError::Rejected("nope".to_string()))
+            .unwrap();
+        journal.resolved(0, true).unwrap();
+        drop(journal);
+
+        // A half written last line is what a crash mid-append leaves
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"entry\":\"resol").unwrap();
+
+        let journal = Journal::resume(&path, "seed", "seeder").unwrap();
+        let payments = journal.payments();
+        assert!(payments[0].is_paid());
+        assert!(payments[1].is_unsettled());
+        assert_eq!(Some(("h1".to_string(), 2)), payments[1].submitted);
+        assert_eq!(Some(false), payments[2].cleared);
+
+        assert_eq!(vec![3], journal.plan(vec![planned("b", "e")]).unwrap());
+        assert_eq!(4, journal.len());
+    }
+
+    #[test]
+    fn test_resume_checks_command_and_missing_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = Journal::path(dir.path(), "collect");
+        assert!(Journal::resume(&path, "collect", "a").unwrap().is_empty());
+        assert!(path.exists());
+        assert!(Journal::resume(&path, "collect", "b").is_err());
+    }
+
+    #[test]
+    fn test_unfinished_and_set_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = Journal::path(dir.path(), "seed");
+        assert!(Journal::reopen(&path).unwrap().is_none());
+
+        let journal = Journal::create(&path, "seed", "seeder").unwrap();
+        journal
+            .plan(vec![planned("a", "b"), planned("a", "c")])
+            .unwrap();
+        journal.submitted(0, "h0".to_string(), 1).unwrap();
+        journal.resolved(0, true).unwrap();
+        assert_eq!(1, journal.unfinished());
+
+        let aside = Journal::set_aside(&path).unwrap();
+        assert!(!path.exists());
+        let earlier = Journal::reopen(&aside).unwrap().unwrap();
+        assert_eq!(1, earlier.unfinished());
+    }
+}
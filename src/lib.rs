@@ -0,0 +1,56 @@
+//! Manages a "bank" of Helium wallets for load testing and bulk
+//! payments. The `helium-load` CLI is a thin layer over this crate.
+//!
+//! ```no_run
+//! use helium_load::{Banker, PasswordSource};
+//!
+//! # fn main() -> helium_load::Result<()> {
+//! let banker = Banker::builder()
+//!     .api_url("https://api.helium.io")
+//!     .password(PasswordSource::Env("PASSWORD".to_string()))
+//!     .working_dir("wallets")
+//!     .threads(8)
+//!     .build()?;
+//!
+//! for balance in banker.balances() {
+//!     println!("{}: {:?}", balance.address, balance.balance);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+#[macro_use]
+extern crate prettytable;
+
+pub mod amount;
+pub mod backend;
+pub mod bank;
+pub mod error;
+pub mod fee;
+pub mod journal;
+pub mod manifest;
+pub mod metrics;
+pub mod mock;
+mod nonce;
+pub mod observer;
+pub mod plan;
+pub mod profile;
+pub mod rate;
+pub mod report;
+pub mod scenario;
+pub mod shutdown;
+pub mod stop;
+pub mod topology;
+pub mod tracker;
+pub mod txn;
+pub mod wallets;
+
+pub use bank::{
+    balance_table, Balance, BalanceSummary, Banker, BankerBuilder, PasswordSource, Payment,
+    PaymentResult, Traffic,
+};
+pub use error::{BankError, Result};
+pub use observer::{ConsoleObserver, Event, Observer};
+pub use shutdown::Shutdown;
+pub use stop::StopConditions;
+pub use wallets::{KeyFile, WalletStore};
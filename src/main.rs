@@ -1,43 +1,283 @@
-#[macro_use]
-extern crate prettytable;
-
-mod bank;
 mod cmd;
 
-use bank::Banker;
 use clap::Clap;
+use cmd::Format;
 use dotenv::dotenv;
-use std::env;
+use helium_load::backend::{ChainBackend, SimBackend, SimConfig};
+use helium_load::mock::{MockConfig, MockServer};
+use helium_load::plan::Plan;
+use helium_load::profile::{LoadProfile, Shape};
+use helium_load::rate::Rate;
+use helium_load::scenario::Scenario;
+use helium_load::{
+    balance_table, BalanceSummary, BankError, Banker, ConsoleObserver, PasswordSource, Result,
+    Shutdown,
+};
+use std::{
+    env,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 fn main() {
     dotenv().ok();
 
     let opts = cmd::Opts::parse();
-    let banker = Banker::new(&api_url(), &password(), &opts.working_dir, opts.threads);
 
-    println!("\n{}\n", banker);
+    if let Err(e) = start(opts) {
+        eprintln!("Error: {}", e);
+        process::exit(e.exit_code());
+    }
+}
 
-    match opts.subcmd {
+fn start(opts: cmd::Opts) -> Result<()> {
+    if let cmd::SubCommand::ServeMock(mock_opts) = &opts.subcmd {
+        let config = MockConfig {
+            latency: Duration::from_millis(mock_opts.latency_ms),
+            error_rate: mock_opts.error_rate,
+            drop_rate: mock_opts.drop_rate,
+            workers: mock_opts.workers,
+        };
+        return MockServer::new(sim_backend(&opts.sim), config)
+            .serve(&mock_opts.listen)
+            .map_err(|e| BankError::Config(format!("Mock server failed: {}", e)));
+    }
+
+    let mut builder = Banker::builder()
+        .working_dir(&opts.working_dir)
+        .threads(opts.threads)
+        .concurrency(opts.concurrency)
+        .shutdown(shutdown_on_signal(opts.drain_timeout)?)
+        .drain_timeout(Duration::from_secs(opts.drain_timeout));
+    if let Some(max_payments) = opts.max_payments {
+        builder = builder.max_payments(max_payments);
+    }
+    // Keep stdout clean when it is being piped into other tools
+    if let cmd::SubCommand::Balances(b) = &opts.subcmd {
+        if b.format != Format::Table {
+            builder = builder.observer(Arc::new(ConsoleObserver::stderr()));
+        }
+    }
+    // Key files are only decrypted for commands that sign or create
+    if !matches!(
+        opts.subcmd,
+        cmd::SubCommand::Balances(_) | cmd::SubCommand::MaxBalance
+    ) {
+        builder = builder.password(PasswordSource::Env("PASSWORD".to_string()));
+    }
+    match opts.backend.as_str() {
+        "sim" => run(builder.build_with(sim_backend(&opts.sim))?, opts),
+        _ => run(builder.api_url(&api_url()?).build()?, opts),
+    }
+}
+
+fn run<B: ChainBackend>(mut banker: Banker<B>, opts: cmd::Opts) -> Result<()> {
+    // Keep stdout clean when it is being piped into other tools
+    match &opts.subcmd {
+        cmd::SubCommand::Balances(b) if b.format != Format::Table => eprintln!("\n{}\n", banker),
+        _ => println!("\n{}\n", banker),
+    }
+
+    let mut command = serde_json::to_value(&opts.subcmd).unwrap_or_default();
+    let spends = !opts.subcmd.planning()
+        && matches!(
+            opts.subcmd,
+            cmd::SubCommand::Apply(_)
+                | cmd::SubCommand::Collect(_)
+                | cmd::SubCommand::Fanout(_)
+                | cmd::SubCommand::Run(_)
+                | cmd::SubCommand::Seed(_)
+                | cmd::SubCommand::SeedIndependent(_)
+                | cmd::SubCommand::Sustained(_)
+        );
+
+    // Metrics and the report are still written when a command fails
+    let result = match opts.subcmd {
+        cmd::SubCommand::Apply(opts) => apply_plan(&banker, &opts),
+        cmd::SubCommand::Collect(opts) if opts.plan.planning() => {
+            save_plan(banker.plan_collect(&opts.address), &opts.plan)
+        }
+        cmd::SubCommand::Fanout(opts) if opts.plan.planning() => {
+            save_plan(banker.plan_fan_out(), &opts.plan)
+        }
+        cmd::SubCommand::Seed(opts) if opts.plan.planning() => {
+            save_plan(banker.plan_seed(&opts.address), &opts.plan)
+        }
+        cmd::SubCommand::SeedIndependent(opts) if opts.plan.planning() => {
+            save_plan(banker.plan_seed_independent(&opts.address), &opts.plan)
+        }
         cmd::SubCommand::Create(opts) => banker.create_wallets(opts.count),
-        cmd::SubCommand::Balances => banker.print_all_balances(),
-        cmd::SubCommand::Collect(opts) => banker.collect(&opts.address),
-        cmd::SubCommand::Fanout => banker.fan_out(),
-        cmd::SubCommand::MaxBalance => {
-            let rich_one = banker.max_bal_wallet();
-            let addr = rich_one.address().unwrap();
-            let bal = banker.get_wallet_balance(&rich_one);
-            println!("Richest Wallet: {}: {}", addr, bal);
+        cmd::SubCommand::Balances(opts) => print_balances(&banker, opts.format),
+        cmd::SubCommand::Collect(opts) => banker
+            .collect(&opts.address, opts.journal_mode())
+            .map(|_| ()),
+        cmd::SubCommand::Fanout(opts) => banker.fan_out(&opts.stop.conditions()),
+        cmd::SubCommand::MaxBalance => banker.max_bal_wallet().map(|rich_one| {
+            println!(
+                "Richest Wallet: {}: {}",
+                rich_one.address,
+                rich_one.balance.unwrap_or(0)
+            );
+        }),
+        cmd::SubCommand::Seed(opts) => banker.seed(&opts.address, opts.journal_mode()).map(|_| ()),
+        cmd::SubCommand::SeedIndependent(opts) => banker
+            .seed_independent(&opts.address, opts.journal_mode())
+            .map(|_| ()),
+        cmd::SubCommand::Run(opts) => {
+            let scenario = Scenario::load(&opts.scenario).map_err(|e| {
+                BankError::Config(format!("Invalid scenario {}: {}", opts.scenario, e))
+            })?;
+            command = serde_json::json!({ "run": scenario });
+            scenario.run(&mut banker)
         }
-        cmd::SubCommand::Seed(opts) => banker.seed(&opts.address),
-        cmd::SubCommand::SeedIndependent(opts) => banker.seed_independent(&opts.address),
-        cmd::SubCommand::Sustained(opts) => banker.pay_forward(opts.count),
+        cmd::SubCommand::ServeMock(_) => unreachable!("serve-mock runs without a banker"),
+        cmd::SubCommand::Sustained(opts) => match opts.tps {
+            Some(tps) => {
+                let rate = Rate::new(tps, opts.burst).map_err(BankError::Config)?;
+                let stop = opts.stop.conditions();
+                banker.pay_at_rate(&rate, opts.profile_duration, &opts.traffic(), &stop)
+            }
+            None => {
+                let (traffic, stop) = (opts.traffic(), opts.stop.conditions());
+                banker.pay_forward(&load_profile(opts)?, &traffic, &stop)
+            }
+        },
+    };
+
+    if banker.stopping() {
+        if let Err(e) = banker.drain() {
+            eprintln!("Failed to drain pending txns: {}", e);
+        }
+    }
+
+    let summary = banker.metrics();
+    if summary.submitted > 0 || !summary.failures.is_empty() {
+        println!("\n{}", summary);
     }
+
+    if spends {
+        let path = report_path(&opts.working_dir, opts.report);
+        match banker.report(command).write(&path) {
+            Ok(()) => println!("Report written to {}", path.display()),
+            Err(e) => println!("Failed to write report {}: {}", path.display(), e),
+        }
+    }
+
+    result
+}
+
+/// Requests a shutdown on the first SIGINT or SIGTERM, so the run
+/// stops sending, drains and still writes its report. A second
+/// signal exits right away.
+fn shutdown_on_signal(drain_secs: u64) -> Result<Shutdown> {
+    let shutdown = Shutdown::new();
+    let handler = shutdown.clone();
+    ctrlc::set_handler(move || {
+        if handler.request() == 1 {
+            eprintln!(
+                "\nStopping, waiting up to {}s for pending txns. Signal again to abort.",
+                drain_secs
+            );
+        } else {
+            eprintln!("\nAborting.");
+            process::exit(130);
+        }
+    })
+    .map_err(|e| BankError::Config(format!("Failed to handle signals: {}", e)))?;
+    Ok(shutdown)
+}
+
+/// Prints a summary of `plan`, writing it to the `--plan` file if given.
+fn save_plan(plan: Result<Plan>, opts: &cmd::PlanOpts) -> Result<()> {
+    let plan = plan?;
+    println!("{}", plan.summary());
+    if let Some(path) = &opts.plan {
+        plan.save(Path::new(path))?;
+        println!("Plan written to {}", path);
+    }
+    Ok(())
+}
+
+/// Sends the payments of a plan file once confirmed.
+fn apply_plan<B: ChainBackend>(banker: &Banker<B>, opts: &cmd::ApplyOpts) -> Result<()> {
+    let plan = Plan::load(Path::new(&opts.plan))?;
+    println!(
+        "Plan for {} made at height {}, now {}",
+        plan.command,
+        plan.height,
+        banker.current_height()?
+    );
+    println!("{}", plan.summary());
+    if !opts.yes && !confirm("Apply this plan?")? {
+        println!("Plan not applied.");
+        return Ok(());
+    }
+    banker.apply(&plan, opts.fresh).map(|_| ())
+}
+
+fn confirm(question: &str) -> Result<bool> {
+    let stdin = |e| BankError::Io(PathBuf::from("stdin"), e);
+    print!("{} [y/N] ", question);
+    io::stdout().flush().map_err(stdin)?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).map_err(stdin)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Prints every wallet's balance followed by totals.
+fn print_balances<B: ChainBackend>(banker: &Banker<B>, format: Format) -> Result<()> {
+    let balances = banker.balances();
+    let summary = BalanceSummary::new(&balances);
+
+    match format {
+        Format::Json => {
+            let out = serde_json::json!({ "balances": balances, "summary": summary });
+            println!("{:#}", out);
+        }
+        Format::Csv => {
+            balance_table(&balances, &summary)
+                .to_csv(io::stdout())
+                .map_err(|e| BankError::Io(PathBuf::from("stdout"), e.into()))?;
+        }
+        Format::Table => {
+            balance_table(&balances, &summary).printstd();
+        }
+    }
+    Ok(())
+}
+
+fn report_path(working_dir: &str, report: Option<String>) -> PathBuf {
+    report.map(PathBuf::from).unwrap_or_else(|| {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        PathBuf::from(working_dir).join(format!("report-{}.json", now))
+    })
+}
+
+fn load_profile(opts: cmd::SustainedOpts) -> Result<LoadProfile> {
+    let r = match (opts.profile, opts.count) {
+        (Some(shape), _) => LoadProfile::new(shape, opts.profile_duration),
+        (None, Some(count)) => LoadProfile::new(Shape::Constant(count), opts.profile_duration),
+        (None, None) => Err("Either a count, --profile or --tps is required.".to_string()),
+    };
+    r.map_err(BankError::Config)
 }
 
-fn api_url() -> String {
-    env::var("API_URL").expect("Missing API_URL env var.")
+fn sim_backend(opts: &cmd::SimOpts) -> SimBackend {
+    let config = SimConfig {
+        block_time: Duration::from_millis(opts.block_ms),
+        block_capacity: opts.block_capacity,
+        fee: opts.fee,
+        max_payments: opts.max_payments,
+    };
+    SimBackend::new(config, opts.genesis.clone())
 }
 
-fn password() -> String {
-    env::var("PASSWORD").expect("Missing PASSWORD env var.")
+fn api_url() -> Result<String> {
+    env::var("API_URL").map_err(|_| BankError::Config("Missing API_URL env var.".to_string()))
 }
@@ -0,0 +1,171 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{BankError, Result};
+
+/// Name of the manifest file kept in the working directory.
+pub const MANIFEST_FILE: &str = "wallets.manifest.json";
+
+/// What the manifest remembers about one key file.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ManifestEntry {
+    pub address: String,
+    /// Modification time of the key file in nanoseconds since the
+    /// epoch when it was indexed. A different mtime means the entry
+    /// is stale.
+    pub modified: u64,
+    /// Creation time of the key file in seconds since the epoch, on
+    /// platforms that record it.
+    pub created: Option<u64>,
+}
+
+/// Maps the key files of a working directory to their addresses, so
+/// an address can be found without opening and decoding every key
+/// file.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Manifest {
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(skip)]
+    dirty: bool,
+    /// Entries keyed by key file name.
+    wallets: BTreeMap<String, ManifestEntry>,
+}
+
+impl Manifest {
+    /// Reads the manifest in `dir`. A missing or unreadable manifest
+    /// yields an empty one that is rebuilt as key files are loaded.
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join(MANIFEST_FILE);
+        let mut manifest: Manifest = fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        manifest.path = path;
+        manifest
+    }
+
+    /// Writes the manifest back if anything changed, via a temporary
+    /// file so a reader never sees it half written.
+    pub fn save(&mut self) -> Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let tmp = self.path.with_extension("tmp");
+        let json = serde_json::to_vec_pretty(self).expect("manifest serializes");
+        fs::write(&tmp, json).map_err(|e| BankError::Io(tmp.clone(), e))?;
+        fs::rename(&tmp, &self.path).map_err(|e| BankError::Io(self.path.clone(), e))?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.wallets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.wallets.is_empty()
+    }
+
+    /// The address of `key_file`, if it was indexed and has not been
+    /// modified since.
+    pub fn address(&self, key_file: &Path) -> Option<&str> {
+        let entry = self.wallets.get(&file_name(key_file))?;
+        if modified(key_file) == Some(entry.modified) {
+            Some(&entry.address)
+        } else {
+            None
+        }
+    }
+
+    /// Records the address of `key_file` as of its current mtime.
+    pub fn insert(&mut self, key_file: &Path, address: &str) {
+        let metadata = match fs::metadata(key_file) {
+            Ok(metadata) => metadata,
+            Err(_) => return,
+        };
+        let entry = ManifestEntry {
+            address: address.to_string(),
+            modified: metadata.modified().map(nanos).unwrap_or_default(),
+            created: metadata.created().ok().map(|t| nanos(t) / 1_000_000_000),
+        };
+        let name = file_name(key_file);
+        if self.wallets.get(&name) != Some(&entry) {
+            self.wallets.insert(name, entry);
+            self.dirty = true;
+        }
+    }
+
+    /// Drops the entries of key files that are no longer in `key_files`.
+    pub fn retain(&mut self, key_files: &[PathBuf]) {
+        let names: HashSet<String> = key_files.iter().map(|p| file_name(p)).collect();
+        let before = self.wallets.len();
+        self.wallets.retain(|name, _| names.contains(name));
+        self.dirty |= self.wallets.len() != before;
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn nanos(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+fn modified(path: &Path) -> Option<u64> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .map(nanos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_round_trip_and_staleness() {
+        let dir = tempfile::tempdir().unwrap();
+        let key_files: Vec<PathBuf> = (1..=2)
+            .map(|i| dir.path().join(format!("wallet_{:05}.key", i)))
+            .collect();
+        for path in &key_files {
+            fs::write(path, b"key").unwrap();
+        }
+
+        let mut manifest = Manifest::load(dir.path());
+        assert!(manifest.is_empty());
+        manifest.insert(&key_files[0], "address-1");
+        manifest.insert(&key_files[1], "address-2");
+        manifest.save().unwrap();
+
+        let mut manifest = Manifest::load(dir.path());
+        assert_eq!(2, manifest.len());
+        assert_eq!(Some("address-1"), manifest.address(&key_files[0]));
+
+        // An entry indexed at a different mtime is stale
+        manifest
+            .wallets
+            .get_mut("wallet_00002.key")
+            .unwrap()
+            .modified -= 1;
+        assert_eq!(None, manifest.address(&key_files[1]));
+        manifest.insert(&key_files[1], "address-2");
+        assert_eq!(Some("address-2"), manifest.address(&key_files[1]));
+
+        manifest.retain(&key_files[..1]);
+        assert_eq!(1, manifest.len());
+        assert!(manifest.dirty);
+    }
+}
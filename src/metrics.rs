@@ -0,0 +1,201 @@
+use std::{collections::BTreeMap, fmt, sync::Mutex, time::Duration};
+
+use serde::Serialize;
+
+use crate::backend::TxnStatus;
+use crate::tracker::TrackedTxn;
+
+/// Collects latency, inclusion and throughput measurements for a run.
+#[derive(Default)]
+pub struct Metrics {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    submitted: u64,
+    cleared: u64,
+    submit_ms: Vec<u64>,
+    inclusion_ms: Vec<u64>,
+    inclusion_blocks: Vec<u64>,
+    // error kind -> count
+    failures: BTreeMap<String, u64>,
+    // height -> txns cleared in that block
+    cleared_per_block: BTreeMap<u64, u64>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records how long a submission took and whether it was accepted.
+    pub fn record_submit(&self, latency: Duration, error: Option<&str>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.submit_ms.push(latency.as_millis() as u64);
+        match error {
+            Some(e) => *inner.failures.entry(error_kind(e)).or_default() += 1,
+            None => inner.submitted += 1,
+        }
+    }
+
+    /// Records a txn that has cleared or failed on chain, `height`
+    /// being the height it was seen resolved at.
+    pub fn record_resolved(&self, txn: &TrackedTxn, height: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        match &txn.status {
+            TxnStatus::Cleared(cleared_height) => {
+                let cleared_height = cleared_height.unwrap_or(height);
+                inner.cleared += 1;
+                *inner.cleared_per_block.entry(cleared_height).or_default() += 1;
+                inner
+                    .inclusion_blocks
+                    .push(cleared_height.saturating_sub(txn.submitted_height));
+                if let Some(elapsed) = txn.time_to_resolve() {
+                    inner.inclusion_ms.push(elapsed.as_millis() as u64);
+                }
+            }
+            TxnStatus::Failed(reason) => {
+                *inner.failures.entry(error_kind(reason)).or_default() += 1
+            }
+            TxnStatus::Pending | TxnStatus::Unknown => {}
+        }
+    }
+
+    pub fn summary(&self) -> Summary {
+        let inner = self.inner.lock().unwrap();
+
+        // Count every block between the first and last inclusion,
+        // including the ones where none of our txns landed.
+        let txns_per_block = match (
+            inner.cleared_per_block.keys().next(),
+            inner.cleared_per_block.keys().last(),
+        ) {
+            (Some(first), Some(last)) => (*first..=*last)
+                .map(|h| inner.cleared_per_block.get(&h).cloned().unwrap_or(0))
+                .collect(),
+            _ => vec![],
+        };
+
+        Summary {
+            submitted: inner.submitted,
+            cleared: inner.cleared,
+            failures: inner.failures.clone(),
+            submit_ms: Percentiles::new(&inner.submit_ms),
+            inclusion_ms: Percentiles::new(&inner.inclusion_ms),
+            inclusion_blocks: Percentiles::new(&inner.inclusion_blocks),
+            txns_per_block: Percentiles::new(&txns_per_block),
+        }
+    }
+}
+
+/// Shortens an error message to the part that identifies its kind,
+/// dropping the addresses, hashes and amounts that follow.
+fn error_kind(message: &str) -> String {
+    message
+        .split(|c| c == ':' || c == '(')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .chars()
+        .take(60)
+        .collect()
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Percentiles {
+    pub count: usize,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+impl Percentiles {
+    pub fn new(values: &[u64]) -> Self {
+        let mut sorted = values.to_vec();
+        sorted.sort_unstable();
+        Self {
+            count: sorted.len(),
+            p50: percentile(&sorted, 50),
+            p90: percentile(&sorted, 90),
+            p99: percentile(&sorted, 99),
+            max: sorted.last().cloned().unwrap_or(0),
+        }
+    }
+}
+
+/// Nearest-rank percentile of already sorted values.
+fn percentile(sorted: &[u64], p: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (p * sorted.len() + 99) / 100;
+    sorted[rank.max(1) - 1]
+}
+
+impl fmt::Display for Percentiles {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "p50 {} / p90 {} / p99 {} / max {} (n={})",
+            self.p50, self.p90, self.p99, self.max, self.count
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Summary {
+    pub submitted: u64,
+    pub cleared: u64,
+    pub failures: BTreeMap<String, u64>,
+    pub submit_ms: Percentiles,
+    pub inclusion_ms: Percentiles,
+    pub inclusion_blocks: Percentiles,
+    pub txns_per_block: Percentiles,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let failed: u64 = self.failures.values().sum();
+        writeln!(
+            f,
+            "Txns: {} submitted, {} cleared, {} failed.",
+            self.submitted, self.cleared, failed
+        )?;
+        writeln!(f, "Submit latency (ms):   {}", self.submit_ms)?;
+        writeln!(f, "Time to clear (ms):    {}", self.inclusion_ms)?;
+        writeln!(f, "Blocks to clear:       {}", self.inclusion_blocks)?;
+        writeln!(f, "Cleared txns/block:    {}", self.txns_per_block)?;
+        for (kind, count) in &self.failures {
+            writeln!(f, "Failed ({}): {}", kind, count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles() {
+        let values: Vec<u64> = (1..=100).collect();
+        let p = Percentiles::new(&values);
+        assert_eq!(100, p.count);
+        assert_eq!(50, p.p50);
+        assert_eq!(90, p.p90);
+        assert_eq!(99, p.p99);
+        assert_eq!(100, p.max);
+
+        assert_eq!(Percentiles::default(), Percentiles::new(&[]));
+        assert_eq!(7, Percentiles::new(&[7]).p99);
+    }
+
+    #[test]
+    fn test_error_kind() {
+        assert_eq!("invalid nonce", error_kind("invalid nonce: 3 for abc"));
+        assert_eq!("Payment failed", error_kind("Payment failed (503)"));
+        assert_eq!("insufficient funds", error_kind("insufficient funds"));
+    }
+}
@@ -0,0 +1,335 @@
+use std::{error::Error, io::Read, net::SocketAddr, sync::Arc, thread, time::Duration};
+
+use helium_proto::{blockchain_txn::Txn, BlockchainTxn};
+use prost::Message;
+use rand::Rng;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::backend::{ChainBackend, SimBackend, TxnStatus};
+use crate::txn::PaymentTxn;
+
+/// An HNT price, in 1/100_000_000 USD, at which a DC costs a bone.
+const BONE_PER_DC_PRICE: u64 = 100_000_000_000;
+
+/// Failure injection for the mock API.
+#[derive(Clone, Debug, Default)]
+pub struct MockConfig {
+    /// Added to every response.
+    pub latency: Duration,
+    /// Fraction of requests answered with a 503.
+    pub error_rate: f64,
+    /// Fraction of submitted txns acknowledged but never added to the ledger.
+    pub drop_rate: f64,
+    /// Number of threads answering requests.
+    pub workers: usize,
+}
+
+/// Serves the subset of the Helium API used by `helium_api::Client`
+/// and `HttpBackend` from a simulated ledger.
+pub struct MockServer {
+    backend: SimBackend,
+    config: MockConfig,
+}
+
+type Reply = (u16, Value);
+
+impl MockServer {
+    pub fn new(backend: SimBackend, config: MockConfig) -> Self {
+        Self { backend, config }
+    }
+
+    /// Blocks forever answering requests on `addr`.
+    pub fn serve(self, addr: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let (mock, addr) = self.spawn(addr)?;
+        println!("Mock Helium API listening on http://{}", addr);
+
+        // Advance the ledger on a timer so heights move between requests
+        let interval = mock
+            .backend
+            .poll_interval()
+            .unwrap_or_else(|| Duration::from_secs(1));
+        let mut last_height = 0;
+        loop {
+            thread::sleep(interval);
+            let height = mock.backend.get_height().unwrap_or(last_height);
+            if height > last_height {
+                println!("Block: {}", height);
+                last_height = height;
+            }
+        }
+    }
+
+    /// Answers requests on `addr` from background threads, returning
+    /// the address listened on. Port 0 picks a free port.
+    pub fn spawn(
+        self,
+        addr: &str,
+    ) -> Result<(Arc<Self>, SocketAddr), Box<dyn Error + Send + Sync>> {
+        let server = Arc::new(Server::http(addr)?);
+        let addr = server.server_addr();
+        let mock = Arc::new(self);
+
+        for _ in 0..mock.config.workers.max(1) {
+            let server = server.clone();
+            let mock = mock.clone();
+            thread::spawn(move || {
+                while let Ok(request) = server.recv() {
+                    mock.handle(request);
+                }
+            });
+        }
+        Ok((mock, addr))
+    }
+
+    fn handle(&self, mut request: Request) {
+        thread::sleep(self.config.latency);
+
+        let (status, body) = if rand::thread_rng().gen::<f64>() < self.config.error_rate {
+            (503, json!({"error": "injected failure"}))
+        } else {
+            self.route(&mut request)
+                .unwrap_or_else(|e| (400, json!({ "error": e.to_string() })))
+        };
+
+        let header = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+        let response = Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(header);
+        let _ = request.respond(response);
+    }
+
+    fn route(&self, request: &mut Request) -> Result<Reply, Box<dyn Error>> {
+        let method = request.method().clone();
+        let url = request.url().to_string();
+        let path: Vec<&str> = url
+            .split('?')
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches("/v1/")
+            .split('/')
+            .collect();
+
+        match (method, path.as_slice()) {
+            (Method::Get, ["blocks", "height"]) => {
+                let height = self.backend.get_height()?;
+                Ok((200, json!({ "data": { "height": height } })))
+            }
+            (Method::Get, ["accounts", address]) => self.account(address),
+            (Method::Get, ["vars"]) => Ok(self.vars()),
+            (Method::Get, ["oracle", "prices", "current"]) => {
+                let height = self.backend.get_height()?;
+                Ok((
+                    200,
+                    json!({ "data": { "price": BONE_PER_DC_PRICE, "block": height } }),
+                ))
+            }
+            (Method::Post, ["pending_transactions"]) => self.submit(request),
+            (Method::Get, ["pending_transactions", hash]) => self.pending_txn(hash),
+            (Method::Get, ["transactions", hash]) => self.txn(hash),
+            _ => Ok(not_found()),
+        }
+    }
+
+    fn account(&self, address: &str) -> Result<Reply, Box<dyn Error>> {
+        let account = self.backend.get_account(address)?;
+        Ok((
+            200,
+            json!({
+                "data": {
+                    "address": account.address,
+                    "balance": account.balance,
+                    "nonce": account.nonce,
+                    "speculative_nonce": account.speculative_nonce,
+                    "dc_balance": 0,
+                    "dc_nonce": 0,
+                    "sec_balance": 0,
+                    "sec_nonce": 0,
+                }
+            }),
+        ))
+    }
+
+    /// Fee variables under which every txn costs the ledger's flat fee:
+    /// a single payload whatever its size, priced a bone per DC.
+    fn vars(&self) -> Reply {
+        (
+            200,
+            json!({
+                "data": {
+                    "txn_fee_multiplier": self.backend.config().fee,
+                    "dc_payload_size": u32::MAX,
+                    "max_payments": self.backend.config().max_payments,
+                }
+            }),
+        )
+    }
+
+    fn submit(&self, request: &mut Request) -> Result<Reply, Box<dyn Error>> {
+        let mut body = String::new();
+        request.as_reader().read_to_string(&mut body)?;
+        let body: Value = serde_json::from_str(&body)?;
+        let encoded = body["txn"].as_str().ok_or("missing txn")?;
+
+        let envelope = BlockchainTxn::decode(&base64::decode(encoded)?[..])?;
+        let txn = match envelope.txn {
+            Some(Txn::PaymentV2(txn)) => PaymentTxn::from_proto(&txn),
+            _ => return Err("only payment_v2 txns are supported".into()),
+        };
+
+        let hash = if rand::thread_rng().gen::<f64>() < self.config.drop_rate {
+            txn.hash()?
+        } else {
+            self.backend.submit_txn(&txn)?
+        };
+        Ok((200, json!({ "data": { "hash": hash } })))
+    }
+
+    fn pending_txn(&self, hash: &str) -> Result<Reply, Box<dyn Error>> {
+        let (status, reason) = match self.backend.get_txn_status(hash)? {
+            TxnStatus::Pending => ("pending", String::new()),
+            TxnStatus::Cleared(_) => ("cleared", String::new()),
+            TxnStatus::Failed(reason) => ("failed", reason),
+            TxnStatus::Unknown => return Ok(not_found()),
+        };
+        Ok((
+            200,
+            json!({
+                "data": [{
+                    "hash": hash,
+                    "type": "payment_v2",
+                    "status": status,
+                    "failed_reason": reason,
+                }]
+            }),
+        ))
+    }
+
+    fn txn(&self, hash: &str) -> Result<Reply, Box<dyn Error>> {
+        match self.backend.get_txn_status(hash)? {
+            TxnStatus::Cleared(Some(height)) => Ok((
+                200,
+                json!({
+                    "data": {
+                        "hash": hash,
+                        "type": "payment_v2",
+                        "height": height,
+                    }
+                }),
+            )),
+            _ => Ok(not_found()),
+        }
+    }
+}
+
+fn not_found() -> Reply {
+    (404, json!({"error": "not found"}))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{HttpBackend, SimConfig};
+    use crate::txn::{address_to_bin, bin_to_address};
+    use std::time::Instant;
+
+    const PAYER: &str = "13Ad3bq7UDGYUG7xkKGAQX3vJkWQ3B5ERR3FGhhvqnEktnRNtw2";
+
+    /// Another valid address, made by changing a byte of `PAYER`.
+    fn payee() -> String {
+        let mut bin = address_to_bin(PAYER).unwrap();
+        bin[32] ^= 1;
+        bin_to_address(&bin)
+    }
+
+    /// An `HttpBackend` talking to a mock of a chain where `PAYER`
+    /// holds 1_000 bones and txns cost a 3 bone fee.
+    fn mock_backend(config: MockConfig) -> HttpBackend {
+        let sim = SimConfig {
+            block_time: Duration::from_millis(50),
+            fee: 3,
+            max_payments: 5,
+            ..SimConfig::default()
+        };
+        let backend = SimBackend::new(sim, vec![(PAYER.to_string(), 1_000)]);
+        let (_, addr) = MockServer::new(backend, config)
+            .spawn("127.0.0.1:0")
+            .unwrap();
+        HttpBackend::new(&format!("http://{}", addr), 2).unwrap()
+    }
+
+    fn payment(nonce: u64) -> PaymentTxn {
+        let mut txn = PaymentTxn::new(PAYER, vec![(payee(), 100)], nonce);
+        txn.fee = 3;
+        txn
+    }
+
+    /// Polls `hash` until it is no longer pending, giving up after
+    /// plenty of blocks.
+    fn settled(backend: &HttpBackend, hash: &str) -> TxnStatus {
+        for _ in 0..200 {
+            match backend.get_txn_status(hash).unwrap() {
+                TxnStatus::Pending => thread::sleep(Duration::from_millis(20)),
+                status => return status,
+            }
+        }
+        panic!("{} never settled", hash);
+    }
+
+    #[test]
+    fn test_routes_through_http_backend() {
+        let backend = mock_backend(MockConfig::default());
+        assert!(backend.get_height().unwrap() >= 1);
+
+        let account = backend.get_account(PAYER).unwrap();
+        assert_eq!(PAYER, account.address);
+        assert_eq!(1_000, account.balance);
+        assert_eq!((0, 0), (account.nonce, account.speculative_nonce));
+
+        let txn = payment(1);
+        assert_eq!(3, backend.txn_fee(&txn).unwrap());
+        assert_eq!(3, backend.fee_in_bones(3).unwrap());
+        assert_eq!(Some(5), backend.max_payments().unwrap());
+
+        let hash = backend.submit_txn(&txn).unwrap();
+        assert_eq!(txn.hash().unwrap(), hash);
+        assert!(matches!(
+            settled(&backend, &hash),
+            TxnStatus::Cleared(Some(_))
+        ));
+        assert_eq!(897, backend.get_account(PAYER).unwrap().balance);
+        assert_eq!(100, backend.get_account(&payee()).unwrap().balance);
+
+        // A stale nonce is refused, and unknown hashes are not found
+        assert!(backend.submit_txn(&payment(1)).is_err());
+        assert_eq!(TxnStatus::Unknown, backend.get_txn_status("nope").unwrap());
+    }
+
+    #[test]
+    fn test_injected_errors_and_latency() {
+        let latency = Duration::from_millis(50);
+        let backend = mock_backend(MockConfig {
+            latency,
+            error_rate: 1.0,
+            ..MockConfig::default()
+        });
+        let started = Instant::now();
+        assert!(backend.get_height().is_err());
+        assert!(started.elapsed() >= latency);
+        assert!(backend.get_account(PAYER).is_err());
+        assert!(backend.submit_txn(&payment(1)).is_err());
+    }
+
+    #[test]
+    fn test_dropped_txns_never_land() {
+        let backend = mock_backend(MockConfig {
+            drop_rate: 1.0,
+            ..MockConfig::default()
+        });
+        let txn = payment(1);
+        let hash = backend.submit_txn(&txn).unwrap();
+        assert_eq!(txn.hash().unwrap(), hash);
+        assert_eq!(TxnStatus::Unknown, backend.get_txn_status(&hash).unwrap());
+        assert_eq!(1_000, backend.get_account(PAYER).unwrap().balance);
+    }
+}
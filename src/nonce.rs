@@ -0,0 +1,85 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::backend::Result;
+
+/// Hands out consecutive nonces per wallet so several payments from
+/// the same wallet can be in flight at once. Each wallet's nonce is
+/// fetched from the chain once and then tracked locally until a
+/// rejection forces a resync.
+#[derive(Default)]
+pub struct NonceManager {
+    // address -> last nonce handed out, `None` when it must be refetched
+    nonces: Mutex<HashMap<String, Arc<Mutex<Option<u64>>>>>,
+}
+
+impl NonceManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the next nonce for `address`. `fetch` supplies the
+    /// chain's speculative nonce when none is known locally; it only
+    /// blocks other payments from the same wallet.
+    pub fn next<F>(&self, address: &str, fetch: F) -> Result<u64>
+    where
+        F: FnOnce() -> Result<u64>,
+    {
+        let entry = self
+            .nonces
+            .lock()
+            .unwrap()
+            .entry(address.to_string())
+            .or_default()
+            .clone();
+
+        let mut nonce = entry.lock().unwrap();
+        let next = match *nonce {
+            Some(last) => last + 1,
+            None => fetch()? + 1,
+        };
+        *nonce = Some(next);
+        Ok(next)
+    }
+
+    /// Forgets the local nonce for `address` so the next payment
+    /// refetches it from the chain.
+    pub fn resync(&self, address: &str) {
+        if let Some(entry) = self.nonces.lock().unwrap().get(address) {
+            *entry.lock().unwrap() = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::BankError;
+
+    #[test]
+    fn test_consecutive_nonces() {
+        let nonces = NonceManager::new();
+        assert_eq!(6, nonces.next("a", || Ok(5)).unwrap());
+        assert_eq!(7, nonces.next("a", || panic!("fetched twice")).unwrap());
+        assert_eq!(1, nonces.next("b", || Ok(0)).unwrap());
+    }
+
+    #[test]
+    fn test_resync_refetches() {
+        let nonces = NonceManager::new();
+        assert_eq!(6, nonces.next("a", || Ok(5)).unwrap());
+        nonces.resync("a");
+        assert_eq!(10, nonces.next("a", || Ok(9)).unwrap());
+    }
+
+    #[test]
+    fn test_fetch_error_is_retried() {
+        let nonces = NonceManager::new();
+        assert!(nonces
+            .next("a", || Err(BankError::Api("offline".to_string())))
+            .is_err());
+        assert_eq!(3, nonces.next("a", || Ok(2)).unwrap());
+    }
+}
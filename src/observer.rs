@@ -0,0 +1,169 @@
+use std::{io, sync::Mutex, time::Duration};
+
+use helium_api::Hnt;
+
+use crate::bank::{balance_table, Balance, BalanceSummary};
+
+/// Something that happened while a `Banker` was working.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Event {
+    /// A scenario moved on to its next phase, counting from 1.
+    PhaseStarted {
+        phase: usize,
+        phases: usize,
+        name: String,
+    },
+    /// A batch of payments is about to be sent.
+    BatchStarted { batch: usize, payments: usize },
+    BatchFinished {
+        batch: usize,
+        payments: usize,
+        elapsed: Duration,
+    },
+    /// `payer` is about to pay out `bones` in total.
+    Paying { payer: String, bones: u64 },
+    /// The backend accepted a payment.
+    PaymentSubmitted {
+        payer: String,
+        hash: String,
+        elapsed: Duration,
+    },
+    /// The payment could not be signed or the backend refused it.
+    PaymentRejected {
+        payer: String,
+        error: String,
+        elapsed: Duration,
+    },
+    /// A submitted payment cleared on chain.
+    PaymentConfirmed { hash: String, height: Option<u64> },
+    /// A submitted payment failed on chain.
+    PaymentFailed { hash: String, reason: String },
+    /// A rate limited run sent `sent` payments in `elapsed`, having
+    /// missed `missed` arrivals because submission could not keep up.
+    Lagging {
+        sent: u64,
+        missed: u64,
+        elapsed: Duration,
+    },
+    /// The chain moved on to a new block.
+    BlockSeen { height: u64 },
+    /// Nothing more can be done until the chain catches up.
+    Waiting { reason: String },
+    /// A wallet or payment was left out, e.g. for an unreadable key file.
+    Skipped { what: String, error: String },
+    /// The balance of every wallet.
+    Balances(Vec<Balance>),
+    /// Anything else worth telling the user.
+    Message(String),
+}
+
+/// Receives the events a `Banker` emits. Events arrive from the
+/// banker's worker threads, so implementations must be thread safe.
+pub trait Observer: Send + Sync {
+    fn on_event(&self, event: &Event);
+}
+
+/// Prints events to stdout the way the CLI always has, or to stderr
+/// when stdout carries output meant for other tools.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ConsoleObserver {
+    stderr: bool,
+}
+
+impl ConsoleObserver {
+    /// A console observer that prints to stderr.
+    pub fn stderr() -> Self {
+        Self { stderr: true }
+    }
+}
+
+impl Observer for ConsoleObserver {
+    fn on_event(&self, event: &Event) {
+        macro_rules! say {
+            ($($arg:tt)*) => {
+                if self.stderr {
+                    eprintln!($($arg)*)
+                } else {
+                    println!($($arg)*)
+                }
+            };
+        }
+
+        match event {
+            Event::PhaseStarted {
+                phase,
+                phases,
+                name,
+            } => say!("Phase {}/{}: {}...", phase, phases, name),
+            Event::BatchStarted { batch, payments } => {
+                say!("Processing batch #{} of {} payments...", batch, payments)
+            }
+            Event::BatchFinished { batch, elapsed, .. } => {
+                say!("Processed batch #{} in: {} ms.", batch, elapsed.as_millis())
+            }
+            Event::Paying { payer, bones } => {
+                say!("Paying out: {} from {}", Hnt::from_bones(*bones), payer)
+            }
+            Event::PaymentSubmitted { hash, elapsed, .. } => {
+                say!("Elapsed Time: {} ms.", elapsed.as_millis());
+                say!("Payment result: Ok({:?})", hash);
+            }
+            Event::PaymentRejected {
+                payer,
+                error,
+                elapsed,
+            } => {
+                say!("Elapsed Time: {} ms.", elapsed.as_millis());
+                say!("Payment from {} had an error: {}", payer, error);
+            }
+            Event::PaymentConfirmed { .. } => {}
+            Event::PaymentFailed { hash, reason } => say!("Txn {} failed: {}", hash, reason),
+            Event::Lagging {
+                sent,
+                missed,
+                elapsed,
+            } => say!(
+                "Falling behind: sent {} txns in {:.1}s ({:.1}/s), missed {} arrivals.",
+                sent,
+                elapsed.as_secs_f64(),
+                *sent as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+                missed
+            ),
+            Event::BlockSeen { height } => say!("Checking Height: {}", height),
+            Event::Waiting { reason } => say!("{}...", reason),
+            Event::Skipped { what, error } => say!("Skipping {}: {}", what, error),
+            Event::Balances(balances) => {
+                let table = balance_table(balances, &BalanceSummary::new(balances));
+                if self.stderr {
+                    let _ = table.print(&mut io::stderr());
+                } else {
+                    table.printstd();
+                }
+            }
+            Event::Message(message) => say!("{}", message),
+        }
+    }
+}
+
+/// Keeps every event in memory, for tests and embedders that
+/// inspect a run once it is over.
+#[derive(Debug, Default)]
+pub struct RecordingObserver {
+    events: Mutex<Vec<Event>>,
+}
+
+impl RecordingObserver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<Event> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+impl Observer for RecordingObserver {
+    fn on_event(&self, event: &Event) {
+        self.events.lock().unwrap().push(event.clone());
+    }
+}
@@ -0,0 +1,204 @@
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{BankError, Result};
+use crate::report::unix_now;
+
+/// One payment of a plan, with the fee it is expected to cost.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PlanPayment {
+    pub payer: String,
+    pub payees: Vec<(String, u64)>,
+    pub fee: u64,
+}
+
+impl PlanPayment {
+    /// Bones paid out, excluding the fee.
+    pub fn bones(&self) -> u64 {
+        self.payees.iter().map(|(_, bones)| bones).sum()
+    }
+}
+
+/// What a wallet holds before a plan is applied and is expected to
+/// hold after. `after` is negative for a wallet the plan overdraws.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PlanBalance {
+    pub address: String,
+    pub before: u64,
+    pub after: i64,
+}
+
+/// The payments a spending command would make, worked out from the
+/// balances at `height` so they can be reviewed before any is sent.
+/// Payments are grouped into stages: a stage is only sent once the
+/// one before it has cleared, as its payers may be paid by it.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Plan {
+    pub command: String,
+    /// The address paid from, or collected into.
+    pub address: Option<String>,
+    pub created_at: u64,
+    pub height: u64,
+    pub stages: Vec<Vec<PlanPayment>>,
+    pub balances: Vec<PlanBalance>,
+}
+
+impl Plan {
+    /// A plan of `stages`, working out each balance after it from
+    /// those in `before`.
+    pub fn new(
+        command: &str,
+        address: Option<&str>,
+        height: u64,
+        stages: Vec<Vec<PlanPayment>>,
+        before: BTreeMap<String, u64>,
+    ) -> Self {
+        let mut after: BTreeMap<&str, i64> = before
+            .iter()
+            .map(|(address, bones)| (address.as_str(), *bones as i64))
+            .collect();
+        for payment in stages.iter().flatten() {
+            *after.entry(payment.payer.as_str()).or_default() -=
+                (payment.bones() + payment.fee) as i64;
+            for (payee, bones) in &payment.payees {
+                *after.entry(payee.as_str()).or_default() += *bones as i64;
+            }
+        }
+        let balances = after
+            .into_iter()
+            .map(|(address, after)| PlanBalance {
+                address: address.to_string(),
+                before: before.get(address).copied().unwrap_or(0),
+                after,
+            })
+            .collect();
+
+        Self {
+            command: command.to_string(),
+            address: address.map(str::to_string),
+            created_at: unix_now(),
+            height,
+            stages,
+            balances,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path).map_err(|e| BankError::Io(path.to_path_buf(), e))?;
+        serde_json::from_slice(&bytes)
+            .map_err(|e| BankError::Config(format!("Invalid plan {}: {}", path.display(), e)))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self).expect("plans serialize");
+        fs::write(path, json).map_err(|e| BankError::Io(path.to_path_buf(), e))
+    }
+
+    pub fn payments(&self) -> impl Iterator<Item = &PlanPayment> {
+        self.stages.iter().flatten()
+    }
+
+    /// Wallets the plan would leave owing bones.
+    pub fn overdrawn(&self) -> Vec<&PlanBalance> {
+        self.balances.iter().filter(|b| b.after < 0).collect()
+    }
+
+    pub fn summary(&self) -> PlanSummary {
+        let mut payers: Vec<&str> = self.payments().map(|p| p.payer.as_str()).collect();
+        payers.sort_unstable();
+        payers.dedup();
+        PlanSummary {
+            payments: self.payments().count(),
+            stages: self.stages.len(),
+            payers: payers.len(),
+            payees: self.payments().map(|p| p.payees.len()).sum(),
+            bones: self.payments().map(PlanPayment::bones).sum(),
+            fees: self.payments().map(|p| p.fee).sum(),
+            overdrawn: self.overdrawn().len(),
+        }
+    }
+}
+
+/// Totals over a plan, shown before it is applied.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct PlanSummary {
+    pub payments: usize,
+    pub stages: usize,
+    pub payers: usize,
+    pub payees: usize,
+    pub bones: u64,
+    pub fees: u64,
+    pub overdrawn: usize,
+}
+
+impl fmt::Display for PlanSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} payments in {} stages from {} payers to {} payees",
+            self.payments, self.stages, self.payers, self.payees
+        )?;
+        write!(f, "{} bones plus {} bones in fees", self.bones, self.fees)?;
+        if self.overdrawn > 0 {
+            write!(
+                f,
+                "\nWarning: {} wallets would be overdrawn",
+                self.overdrawn
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payment(payer: &str, payees: &[(&str, u64)], fee: u64) -> PlanPayment {
+        PlanPayment {
+            payer: payer.to_string(),
+            payees: payees.iter().map(|(a, b)| (a.to_string(), *b)).collect(),
+            fee,
+        }
+    }
+
+    #[test]
+    fn test_plan_balances_and_summary() {
+        let before = vec![("a".to_string(), 100), ("b".to_string(), 5)]
+            .into_iter()
+            .collect();
+        let stages = vec![
+            vec![payment("a", &[("b", 30), ("c", 30)], 2)],
+            vec![payment("b", &[("d", 40)], 1)],
+        ];
+        let plan = Plan::new("seed-independent", Some("a"), 10, stages, before);
+
+        let after: Vec<(&str, u64, i64)> = plan
+            .balances
+            .iter()
+            .map(|b| (b.address.as_str(), b.before, b.after))
+            .collect();
+        assert_eq!(
+            vec![("a", 100, 38), ("b", 5, -6), ("c", 0, 30), ("d", 0, 40)],
+            after
+        );
+        assert_eq!(
+            PlanSummary {
+                payments: 2,
+                stages: 2,
+                payers: 2,
+                payees: 3,
+                bones: 100,
+                fees: 3,
+                overdrawn: 1,
+            },
+            plan.summary()
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plan.json");
+        plan.save(&path).unwrap();
+        assert_eq!(plan, Plan::load(&path).unwrap());
+    }
+}
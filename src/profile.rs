@@ -0,0 +1,311 @@
+use std::{f64::consts::PI, fmt, str::FromStr, time::Duration};
+
+use serde::Serialize;
+
+/// A length of a run, either in blocks or in wall time.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum Span {
+    Blocks(u64),
+    Secs(u64),
+}
+
+impl Span {
+    /// How many of this span have passed at `progress`.
+    fn fraction(&self, progress: &Progress) -> f64 {
+        match self {
+            Span::Blocks(n) => progress.blocks as f64 / (*n).max(1) as f64,
+            Span::Secs(n) => progress.elapsed.as_secs_f64() / (*n).max(1) as f64,
+        }
+    }
+
+    /// True once a whole span has passed at `progress`.
+    pub fn has_passed(&self, progress: &Progress) -> bool {
+        self.fraction(progress) >= 1.0
+    }
+
+    fn same_unit(&self, other: &Span) -> bool {
+        matches!(
+            (self, other),
+            (Span::Blocks(_), Span::Blocks(_)) | (Span::Secs(_), Span::Secs(_))
+        )
+    }
+
+    fn amount(&self) -> u64 {
+        match self {
+            Span::Blocks(n) | Span::Secs(n) => *n,
+        }
+    }
+}
+
+/// Parses `30b` as 30 blocks and `90s`, `15m` or `2h` as wall time.
+impl FromStr for Span {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let split = s.rfind(|c: char| c.is_ascii_digit()).map_or(0, |i| i + 1);
+        let (amount, unit) = s.split_at(split);
+        let amount: u64 = amount
+            .parse()
+            .map_err(|_| format!("invalid span {}, expected e.g. 30b or 15m", s))?;
+        match unit {
+            "b" => Ok(Span::Blocks(amount)),
+            "s" => Ok(Span::Secs(amount)),
+            "m" => Ok(Span::Secs(amount * 60)),
+            "h" => Ok(Span::Secs(amount * 3600)),
+            _ => Err(format!("invalid span unit in {}, expected b, s, m or h", s)),
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Span::Blocks(n) => write!(f, "{} blocks", n),
+            Span::Secs(n) => write!(f, "{} s", n),
+        }
+    }
+}
+
+/// How far into a run we are.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Progress {
+    pub blocks: u64,
+    pub elapsed: Duration,
+}
+
+/// The shape of the load over time, in payments per block.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Shape {
+    Constant(usize),
+    /// Linear from `from` to `to` over the profile duration.
+    Ramp {
+        from: usize,
+        to: usize,
+    },
+    /// Starts at `start` and adds `step` after each `every`.
+    Step {
+        start: usize,
+        step: usize,
+        every: Span,
+    },
+    /// Runs at `base` with `peak` for `length` at the start of each `every`.
+    Spike {
+        base: usize,
+        peak: usize,
+        every: Span,
+        length: Span,
+    },
+    /// Oscillates around `base` by `amplitude` once per `period`.
+    Sine {
+        base: usize,
+        amplitude: usize,
+        period: Span,
+    },
+    /// A constant rate meant to be held for a long duration.
+    Soak(usize),
+}
+
+/// Parses `constant:N`, `ramp:FROM:TO`, `step:START:STEP:EVERY`,
+/// `spike:BASE:PEAK:EVERY:LENGTH`, `sine:BASE:AMPLITUDE:PERIOD` or
+/// `soak:N`, where spans look like `10b` or `5m`.
+impl FromStr for Shape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let count = |i: usize| -> Result<usize, String> {
+            parts
+                .get(i)
+                .ok_or_else(|| format!("missing value in profile {}", s))?
+                .parse()
+                .map_err(|_| format!("invalid count in profile {}", s))
+        };
+        let span = |i: usize| -> Result<Span, String> {
+            parts
+                .get(i)
+                .ok_or_else(|| format!("missing span in profile {}", s))?
+                .parse()
+        };
+
+        let (shape, arity) = match parts[0] {
+            "constant" => (Shape::Constant(count(1)?), 2),
+            "soak" => (Shape::Soak(count(1)?), 2),
+            "ramp" => (
+                Shape::Ramp {
+                    from: count(1)?,
+                    to: count(2)?,
+                },
+                3,
+            ),
+            "step" => (
+                Shape::Step {
+                    start: count(1)?,
+                    step: count(2)?,
+                    every: span(3)?,
+                },
+                4,
+            ),
+            "spike" => {
+                let (every, length) = (span(3)?, span(4)?);
+                if !every.same_unit(&length) {
+                    return Err(format!("spike spans must use the same unit in {}", s));
+                }
+                (
+                    Shape::Spike {
+                        base: count(1)?,
+                        peak: count(2)?,
+                        every,
+                        length,
+                    },
+                    5,
+                )
+            }
+            "sine" => (
+                Shape::Sine {
+                    base: count(1)?,
+                    amplitude: count(2)?,
+                    period: span(3)?,
+                },
+                4,
+            ),
+            other => return Err(format!("unknown profile {}", other)),
+        };
+
+        if parts.len() != arity {
+            return Err(format!("wrong number of values in profile {}", s));
+        }
+        Ok(shape)
+    }
+}
+
+/// A load shape held for an optional duration.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LoadProfile {
+    pub shape: Shape,
+    pub duration: Option<Span>,
+}
+
+impl LoadProfile {
+    pub fn new(shape: Shape, duration: Option<Span>) -> Result<Self, String> {
+        if let (Shape::Ramp { .. }, None) = (&shape, duration) {
+            return Err("a ramp profile needs a duration".to_string());
+        }
+        Ok(Self { shape, duration })
+    }
+
+    /// True once the profile duration has passed.
+    pub fn finished(&self, progress: &Progress) -> bool {
+        self.duration.map_or(false, |d| d.has_passed(progress))
+    }
+
+    /// The number of payments to submit in the block at `progress`.
+    pub fn rate(&self, progress: &Progress) -> usize {
+        match &self.shape {
+            Shape::Constant(n) | Shape::Soak(n) => *n,
+            Shape::Ramp { from, to } => {
+                let f = self.duration.map_or(1.0, |d| d.fraction(progress)).min(1.0);
+                (*from as f64 + (*to as f64 - *from as f64) * f).round() as usize
+            }
+            Shape::Step { start, step, every } => {
+                start + step * every.fraction(progress).floor() as usize
+            }
+            Shape::Spike {
+                base,
+                peak,
+                every,
+                length,
+            } => {
+                let into_cycle = every.fraction(progress).fract() * every.amount() as f64;
+                if into_cycle < length.amount() as f64 {
+                    *peak
+                } else {
+                    *base
+                }
+            }
+            Shape::Sine {
+                base,
+                amplitude,
+                period,
+            } => {
+                let wave = (2.0 * PI * period.fraction(progress)).sin();
+                (*base as f64 + *amplitude as f64 * wave).round().max(0.0) as usize
+            }
+        }
+    }
+}
+
+impl fmt::Display for LoadProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.shape)?;
+        if let Some(duration) = self.duration {
+            write!(f, " for {}", duration)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at_block(blocks: u64) -> Progress {
+        Progress {
+            blocks,
+            elapsed: Duration::default(),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Ok(Span::Blocks(30)), "30b".parse());
+        assert_eq!(Ok(Span::Secs(900)), "15m".parse());
+        assert!("15x".parse::<Span>().is_err());
+
+        assert_eq!(
+            Ok(Shape::Step {
+                start: 10,
+                step: 5,
+                every: Span::Blocks(3)
+            }),
+            "step:10:5:3b".parse()
+        );
+        assert!("spike:1:9:10b:1m".parse::<Shape>().is_err());
+        assert!("ramp:1".parse::<Shape>().is_err());
+        assert!("ramp:1:2:3".parse::<Shape>().is_err());
+    }
+
+    #[test]
+    fn test_ramp() {
+        let p =
+            LoadProfile::new(Shape::Ramp { from: 10, to: 110 }, Some(Span::Blocks(10))).unwrap();
+        assert_eq!(10, p.rate(&at_block(0)));
+        assert_eq!(60, p.rate(&at_block(5)));
+        assert_eq!(110, p.rate(&at_block(10)));
+        assert!(!p.finished(&at_block(9)));
+        assert!(p.finished(&at_block(10)));
+
+        assert!(LoadProfile::new(Shape::Ramp { from: 1, to: 2 }, None).is_err());
+    }
+
+    #[test]
+    fn test_step_and_spike() {
+        let step = LoadProfile::new("step:10:5:3b".parse().unwrap(), None).unwrap();
+        assert_eq!(10, step.rate(&at_block(2)));
+        assert_eq!(15, step.rate(&at_block(3)));
+        assert_eq!(20, step.rate(&at_block(7)));
+
+        let spike = LoadProfile::new("spike:5:50:10b:2b".parse().unwrap(), None).unwrap();
+        assert_eq!(50, spike.rate(&at_block(0)));
+        assert_eq!(50, spike.rate(&at_block(1)));
+        assert_eq!(5, spike.rate(&at_block(2)));
+        assert_eq!(50, spike.rate(&at_block(11)));
+    }
+
+    #[test]
+    fn test_sine() {
+        let sine = LoadProfile::new("sine:100:50:4b".parse().unwrap(), None).unwrap();
+        assert_eq!(100, sine.rate(&at_block(0)));
+        assert_eq!(150, sine.rate(&at_block(1)));
+        assert_eq!(50, sine.rate(&at_block(3)));
+    }
+}
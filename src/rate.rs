@@ -0,0 +1,118 @@
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// An open-loop arrival rate: `tps` payments per second, with up to
+/// `burst` sent at once to catch up after a slow submission.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Rate {
+    pub tps: f64,
+    pub burst: usize,
+}
+
+impl Rate {
+    /// A rate of `tps` whose burst defaults to one second of arrivals.
+    pub fn new(tps: f64, burst: Option<usize>) -> Result<Self, String> {
+        if tps.is_nan() || tps <= 0.0 {
+            return Err(format!("--tps must be positive, got {}", tps));
+        }
+        let burst = burst.unwrap_or_else(|| tps.ceil() as usize).max(1);
+        Ok(Self { tps, burst })
+    }
+}
+
+/// Hands out one token per arrival at a fixed rate, holding at most
+/// `burst` of them. Arrivals that would overflow a full bucket are
+/// counted as missed: the submitter could not keep up with them.
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    missed: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    /// A bucket that starts full at `now`.
+    pub fn new(rate: &Rate, now: Instant) -> Self {
+        Self {
+            rate: rate.tps,
+            burst: rate.burst as f64,
+            tokens: rate.burst as f64,
+            missed: 0.0,
+            last: now,
+        }
+    }
+
+    /// Takes every whole token that has accrued by `now`.
+    pub fn take(&mut self, now: Instant) -> usize {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.tokens += elapsed * self.rate;
+        if self.tokens > self.burst {
+            self.missed += self.tokens - self.burst;
+            self.tokens = self.burst;
+        }
+        let taken = self.tokens.floor();
+        self.tokens -= taken;
+        taken as usize
+    }
+
+    /// How long until the next whole token accrues.
+    pub fn next_token(&self) -> Duration {
+        Duration::from_secs_f64((1.0 - self.tokens).max(0.0) / self.rate)
+    }
+
+    /// Arrivals dropped so far because the bucket was full.
+    pub fn missed(&self) -> u64 {
+        self.missed.floor() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_defaults_burst_to_one_second() {
+        assert_eq!(20, Rate::new(19.5, None).unwrap().burst);
+        assert_eq!(1, Rate::new(0.5, None).unwrap().burst);
+        assert_eq!(5, Rate::new(100.0, Some(5)).unwrap().burst);
+        assert!(Rate::new(0.0, None).is_err());
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let rate = Rate::new(10.0, Some(5)).unwrap();
+        let mut bucket = TokenBucket::new(&rate, start);
+
+        // Starts full, then refills at 10 per second
+        assert_eq!(5, bucket.take(start));
+        assert_eq!(0, bucket.take(start));
+        assert_eq!(Duration::from_millis(100), bucket.next_token());
+        assert_eq!(2, bucket.take(start + Duration::from_millis(250)));
+        assert_eq!(Duration::from_millis(50), bucket.next_token());
+        assert_eq!(0, bucket.missed());
+
+        // A stall longer than the burst drops the overflow
+        assert_eq!(5, bucket.take(start + Duration::from_millis(1250)));
+        assert_eq!(5, bucket.missed());
+    }
+
+    #[test]
+    fn test_token_bucket_over_a_second() {
+        let start = Instant::now();
+        let rate = Rate::new(40.0, Some(4)).unwrap();
+        let mut bucket = TokenBucket::new(&rate, start);
+
+        // A full bucket of 4 plus 40 arrivals over the second, taken
+        // as often as they accrue
+        let taken: usize = (0..=10)
+            .map(|i| bucket.take(start + Duration::from_millis(100 * i)))
+            .sum();
+        assert_eq!(44, taken);
+        assert_eq!(0, bucket.missed());
+    }
+}
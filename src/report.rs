@@ -0,0 +1,185 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    path::Path,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::backend::TxnStatus;
+use crate::metrics::Summary;
+use crate::tracker::TrackedTxn;
+
+/// Everything known about a load run, written as JSON when it ends.
+#[derive(Debug, Serialize)]
+pub struct RunReport {
+    pub config: RunConfig,
+    pub started_at: u64,
+    pub ended_at: u64,
+    pub start_height: u64,
+    pub end_height: u64,
+    pub phases: Vec<PhaseReport>,
+    pub batches: Vec<BatchReport>,
+    pub wallets: BTreeMap<String, WalletTxns>,
+    pub errors: Vec<TxnError>,
+    pub summary: Summary,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunConfig {
+    pub command: Value,
+    pub backend: String,
+    pub working_dir: String,
+    pub threads: usize,
+    pub wallets: usize,
+}
+
+/// One phase of a scenario run.
+#[derive(Clone, Debug, Serialize)]
+pub struct PhaseReport {
+    pub name: String,
+    pub started_at: u64,
+    pub ended_at: u64,
+    pub start_height: u64,
+    pub end_height: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BatchReport {
+    pub batch: usize,
+    pub height: u64,
+    /// Payments submitted, leaving out those skipped or rejected.
+    pub payments: usize,
+    pub elapsed_ms: u64,
+}
+
+/// Txn counts for a single payer.
+#[derive(Debug, Default, Serialize)]
+pub struct WalletTxns {
+    pub submitted: u64,
+    pub rejected: u64,
+    pub cleared: u64,
+    pub failed: u64,
+}
+
+/// A payment that was rejected on submission or failed on chain.
+#[derive(Clone, Debug, Serialize)]
+pub struct TxnError {
+    pub payer: String,
+    pub hash: Option<String>,
+    pub error: String,
+}
+
+/// Collects the parts of a report that only the running commands
+/// know about, such as batch timings and rejected submissions.
+pub struct RunLog {
+    started_at: u64,
+    start_height: Mutex<Option<u64>>,
+    phases: Mutex<Vec<PhaseReport>>,
+    batches: Mutex<Vec<BatchReport>>,
+    rejected: Mutex<Vec<TxnError>>,
+}
+
+impl Default for RunLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RunLog {
+    pub fn new() -> Self {
+        Self {
+            started_at: unix_now(),
+            start_height: Mutex::new(None),
+            phases: Mutex::new(vec![]),
+            batches: Mutex::new(vec![]),
+            rejected: Mutex::new(vec![]),
+        }
+    }
+
+    /// Remembers the first height seen during the run.
+    pub fn saw_height(&self, height: u64) {
+        self.start_height.lock().unwrap().get_or_insert(height);
+    }
+
+    pub fn record_phase(&self, phase: PhaseReport) {
+        self.phases.lock().unwrap().push(phase);
+    }
+
+    pub fn record_batch(&self, batch: BatchReport) {
+        self.batches.lock().unwrap().push(batch);
+    }
+
+    pub fn record_rejected(&self, payer: &str, error: &str) {
+        self.rejected.lock().unwrap().push(TxnError {
+            payer: payer.to_string(),
+            hash: None,
+            error: error.to_string(),
+        });
+    }
+
+    pub fn report(
+        &self,
+        config: RunConfig,
+        end_height: u64,
+        txns: &[TrackedTxn],
+        summary: Summary,
+    ) -> RunReport {
+        let rejected = self.rejected.lock().unwrap().clone();
+
+        let mut wallets: BTreeMap<String, WalletTxns> = BTreeMap::new();
+        for error in &rejected {
+            wallets.entry(error.payer.clone()).or_default().rejected += 1;
+        }
+
+        let mut errors = rejected;
+        for txn in txns {
+            let wallet = wallets.entry(txn.payer.clone()).or_default();
+            wallet.submitted += 1;
+            match &txn.status {
+                TxnStatus::Cleared(_) => wallet.cleared += 1,
+                TxnStatus::Failed(reason) => {
+                    wallet.failed += 1;
+                    errors.push(TxnError {
+                        payer: txn.payer.clone(),
+                        hash: Some(txn.hash.clone()),
+                        error: reason.clone(),
+                    });
+                }
+                TxnStatus::Pending | TxnStatus::Unknown => {}
+            }
+        }
+
+        RunReport {
+            config,
+            started_at: self.started_at,
+            ended_at: unix_now(),
+            start_height: self.start_height.lock().unwrap().unwrap_or(end_height),
+            end_height,
+            phases: self.phases.lock().unwrap().clone(),
+            batches: self.batches.lock().unwrap().clone(),
+            wallets,
+            errors,
+            summary,
+        }
+    }
+}
+
+impl RunReport {
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let file = fs::File::create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,299 @@
+use std::{error::Error, fmt::Display, fs, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use crate::amount::Amount;
+use crate::backend::ChainBackend;
+use crate::bank::{Banker, Traffic};
+use crate::error::{BankError, Result};
+use crate::journal::JournalMode;
+use crate::observer::Event;
+use crate::profile::{LoadProfile, Shape, Span};
+use crate::report::{unix_now, PhaseReport};
+use crate::stop::StopConditions;
+use crate::topology::Topology;
+
+/// A whole load test described in a TOML file as a list of phases
+/// run one after another against the same banker:
+///
+/// ```toml
+/// name = "ramp to 200"
+///
+/// [[phase]]
+/// type = "create"
+/// count = 100
+///
+/// [[phase]]
+/// type = "seed-independent"
+/// address = "13Ad3bq7..."
+///
+/// [[phase]]
+/// type = "wait-funded"
+/// timeout = "30m"
+///
+/// [[phase]]
+/// type = "sustained"
+/// profile = "ramp:10:200"
+/// duration = "60b"
+/// topology = "zipf:1.2"
+/// amount = "uniform:1:100"
+/// seed = 42
+///
+/// [[phase]]
+/// type = "collect"
+/// address = "13Ad3bq7..."
+/// ```
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Scenario {
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "phase")]
+    pub phases: Vec<Phase>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Phase {
+    Create {
+        count: usize,
+    },
+    Seed {
+        address: String,
+    },
+    SeedIndependent {
+        address: String,
+    },
+    /// Waits until every wallet holds at least `min_bones`.
+    WaitFunded {
+        #[serde(default = "default_min_bones")]
+        min_bones: u64,
+        #[serde(default, deserialize_with = "parse_opt")]
+        timeout: Option<Span>,
+    },
+    /// Sustained load, either a fixed `count` per block or a `profile`,
+    /// between wallets as laid out by `topology` (a ring by default),
+    /// paying `amount` (a bone by default).
+    Sustained {
+        count: Option<usize>,
+        #[serde(default, deserialize_with = "parse_opt")]
+        profile: Option<Shape>,
+        #[serde(deserialize_with = "parse")]
+        duration: Span,
+        #[serde(default, deserialize_with = "parse_opt")]
+        topology: Option<Topology>,
+        #[serde(default, deserialize_with = "parse_opt")]
+        amount: Option<Amount>,
+        seed: Option<u64>,
+    },
+    Collect {
+        address: String,
+    },
+}
+
+fn default_min_bones() -> u64 {
+    1
+}
+
+fn parse<'de, D, T>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(de::Error::custom)
+}
+
+fn parse_opt<'de, D, T>(deserializer: D) -> std::result::Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| s.parse().map_err(de::Error::custom))
+        .transpose()
+}
+
+impl Phase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Create { .. } => "create",
+            Phase::Seed { .. } => "seed",
+            Phase::SeedIndependent { .. } => "seed-independent",
+            Phase::WaitFunded { .. } => "wait-funded",
+            Phase::Sustained { .. } => "sustained",
+            Phase::Collect { .. } => "collect",
+        }
+    }
+
+    fn profile(&self) -> std::result::Result<Option<LoadProfile>, String> {
+        match self {
+            Phase::Sustained {
+                count,
+                profile,
+                duration,
+                ..
+            } => {
+                let shape = match (profile, count) {
+                    (Some(shape), _) => shape.clone(),
+                    (None, Some(count)) => Shape::Constant(*count),
+                    (None, None) => {
+                        return Err("a sustained phase needs a count or profile".to_string())
+                    }
+                };
+                LoadProfile::new(shape, Some(*duration)).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+impl Scenario {
+    pub fn load(path: &str) -> std::result::Result<Self, Box<dyn Error>> {
+        let scenario: Scenario = toml::from_str(&fs::read_to_string(path)?)?;
+        for phase in &scenario.phases {
+            phase.profile()?;
+        }
+        Ok(scenario)
+    }
+
+    /// Runs every phase in order, stopping at the first that fails.
+    pub fn run<B: ChainBackend>(&self, banker: &mut Banker<B>) -> Result<()> {
+        for (i, phase) in self.phases.iter().enumerate() {
+            if banker.stopping() {
+                banker.emit(Event::Message(format!(
+                    "Stopping before phase {} of {}.",
+                    i + 1,
+                    self.phases.len()
+                )));
+                break;
+            }
+            banker.emit(Event::PhaseStarted {
+                phase: i + 1,
+                phases: self.phases.len(),
+                name: phase.name().to_string(),
+            });
+            let started_at = unix_now();
+            let start_height = banker.current_height()?;
+
+            match phase {
+                Phase::Create { count } => {
+                    banker.create_wallets(*count)?;
+                    banker.reload_wallets()?;
+                }
+                Phase::Seed { address } => {
+                    banker.seed(address, JournalMode::New)?;
+                }
+                Phase::SeedIndependent { address } => {
+                    banker.seed_independent(address, JournalMode::New)?;
+                }
+                Phase::WaitFunded { min_bones, timeout } => {
+                    if !banker.wait_until_funded(*min_bones, *timeout)? && !banker.stopping() {
+                        return Err(BankError::Timeout(format!(
+                            "wallets were not funded in time ({:?})",
+                            timeout
+                        )));
+                    }
+                }
+                Phase::Sustained {
+                    topology,
+                    amount,
+                    seed,
+                    ..
+                } => {
+                    if let Some(profile) = phase.profile().map_err(BankError::Config)? {
+                        let traffic = Traffic {
+                            topology: topology.clone().unwrap_or_default(),
+                            amount: amount.clone().unwrap_or_default(),
+                            seed: *seed,
+                        };
+                        banker.pay_forward(&profile, &traffic, &StopConditions::default())?;
+                    }
+                }
+                Phase::Collect { address } => {
+                    banker.collect(address, JournalMode::New)?;
+                }
+            }
+
+            banker.record_phase(PhaseReport {
+                name: phase.name().to_string(),
+                started_at,
+                ended_at: unix_now(),
+                start_height,
+                end_height: banker.current_height()?,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scenario() {
+        let scenario: Scenario = toml::from_str(
+            r#"
+            name = "ramp"
+
+            [[phase]]
+            type = "create"
+            count = 10
+
+            [[phase]]
+            type = "wait-funded"
+            timeout = "5m"
+
+            [[phase]]
+            type = "sustained"
+            profile = "ramp:1:10"
+            duration = "20b"
+            topology = "fanout:5"
+            amount = "percent:5"
+            seed = 7
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!("ramp", scenario.name);
+        assert_eq!(3, scenario.phases.len());
+        match &scenario.phases[1] {
+            Phase::WaitFunded { min_bones, timeout } => {
+                assert_eq!(1, *min_bones);
+                assert_eq!(Some(Span::Secs(300)), *timeout);
+            }
+            phase => panic!("unexpected phase {:?}", phase),
+        }
+        assert_eq!(
+            Some(
+                LoadProfile::new(Shape::Ramp { from: 1, to: 10 }, Some(Span::Blocks(20))).unwrap()
+            ),
+            scenario.phases[2].profile().unwrap()
+        );
+        assert!(matches!(
+            scenario.phases[2],
+            Phase::Sustained {
+                topology: Some(Topology::FanOut(5)),
+                amount: Some(Amount::Percent(_)),
+                seed: Some(7),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_sustained_needs_count_or_profile() {
+        let scenario: Scenario = toml::from_str(
+            r#"
+            [[phase]]
+            type = "sustained"
+            duration = "20b"
+            "#,
+        )
+        .unwrap();
+        assert!(scenario.phases[0].profile().is_err());
+    }
+}
@@ -0,0 +1,58 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+/// A request to stop a run early, shared between a banker and
+/// whatever asks it to stop, such as a signal handler. Long running
+/// commands check it between batches and stop scheduling payments
+/// once it has been requested.
+#[derive(Clone, Debug, Default)]
+pub struct Shutdown {
+    requests: Arc<AtomicUsize>,
+    /// When the first request came in, which drain timeouts count from.
+    since: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks the run to stop, returning how many times that has been
+    /// asked so far, this time included.
+    pub fn request(&self) -> usize {
+        self.since.lock().unwrap().get_or_insert_with(Instant::now);
+        self.requests.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    pub fn requested(&self) -> bool {
+        self.requests.load(Ordering::SeqCst) > 0
+    }
+
+    /// When a shutdown was first requested, if it has been.
+    pub fn requested_at(&self) -> Option<Instant> {
+        *self.since.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_are_shared_and_counted() {
+        let shutdown = Shutdown::new();
+        let handler = shutdown.clone();
+        assert!(!shutdown.requested());
+        assert_eq!(None, shutdown.requested_at());
+        assert_eq!(1, handler.request());
+        assert!(shutdown.requested());
+        let first = shutdown.requested_at().unwrap();
+        assert_eq!(2, handler.request());
+        assert_eq!(Some(first), shutdown.requested_at());
+    }
+}
@@ -0,0 +1,125 @@
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// When a command that would otherwise run forever should finish.
+/// Conditions are checked between batches, so a run can overshoot
+/// them by up to one batch. A run stops once any of them is reached.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct StopConditions {
+    /// Wall time to run for.
+    pub duration: Option<Duration>,
+    /// Blocks to run for, counted from the height at the start.
+    pub blocks: Option<u64>,
+    /// Txns the chain may accept before stopping.
+    pub max_txns: Option<u64>,
+    /// Chain height to stop at.
+    pub until_height: Option<u64>,
+    /// Bones, fees included, the run may spend before stopping.
+    pub max_spend: Option<u64>,
+}
+
+/// Where a run stands, as measured by `StopConditions`.
+#[derive(Clone, Copy, Debug)]
+pub struct Checkpoint {
+    pub at: Instant,
+    pub height: u64,
+    /// Txns accepted by the chain so far.
+    pub txns: u64,
+    /// Bones spent so far, fees included.
+    pub spent: u64,
+}
+
+impl StopConditions {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Why a run that started at `start` and has reached `now` should
+    /// stop, if it should.
+    pub fn reached(&self, start: &Checkpoint, now: &Checkpoint) -> Option<String> {
+        let elapsed = now.at.saturating_duration_since(start.at);
+        let blocks = now.height.saturating_sub(start.height);
+        let txns = now.txns.saturating_sub(start.txns);
+        let spent = now.spent.saturating_sub(start.spent);
+
+        if let Some(duration) = self.duration.filter(|d| elapsed >= *d) {
+            return Some(format!("ran for {}s", duration.as_secs()));
+        }
+        if let Some(n) = self.blocks.filter(|n| blocks >= *n) {
+            return Some(format!("ran for {} blocks", n));
+        }
+        if let Some(n) = self.max_txns.filter(|n| txns >= *n) {
+            return Some(format!("sent {} of at most {} txns", txns, n));
+        }
+        if let Some(height) = self.until_height.filter(|h| now.height >= *h) {
+            return Some(format!("reached height {}", height));
+        }
+        if let Some(bones) = self.max_spend.filter(|b| spent >= *b) {
+            return Some(format!("spent {} of at most {} bones", spent, bones));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint(at: Instant, height: u64, txns: u64, spent: u64) -> Checkpoint {
+        Checkpoint {
+            at,
+            height,
+            txns,
+            spent,
+        }
+    }
+
+    #[test]
+    fn test_no_conditions_never_stop() {
+        let start = checkpoint(Instant::now(), 10, 0, 0);
+        let now = checkpoint(start.at + Duration::from_secs(3600), 1_000, 1_000, 1_000);
+        let stop = StopConditions::default();
+        assert!(stop.is_empty());
+        assert_eq!(None, stop.reached(&start, &now));
+    }
+
+    #[test]
+    fn test_conditions_count_from_the_start() {
+        let start = checkpoint(Instant::now(), 100, 20, 500);
+        let at = |secs, height, txns, spent| {
+            checkpoint(start.at + Duration::from_secs(secs), height, txns, spent)
+        };
+
+        let stop = StopConditions {
+            duration: Some(Duration::from_secs(60)),
+            ..StopConditions::default()
+        };
+        assert_eq!(None, stop.reached(&start, &at(59, 200, 0, 0)));
+        assert!(stop.reached(&start, &at(60, 100, 20, 500)).is_some());
+
+        let stop = StopConditions {
+            blocks: Some(5),
+            max_txns: Some(10),
+            ..StopConditions::default()
+        };
+        assert_eq!(None, stop.reached(&start, &at(0, 104, 29, 0)));
+        assert_eq!(
+            Some("ran for 5 blocks".to_string()),
+            stop.reached(&start, &at(0, 105, 29, 0))
+        );
+        assert_eq!(
+            Some("sent 10 of at most 10 txns".to_string()),
+            stop.reached(&start, &at(0, 104, 30, 0))
+        );
+
+        let stop = StopConditions {
+            until_height: Some(150),
+            max_spend: Some(1_000),
+            ..StopConditions::default()
+        };
+        assert_eq!(None, stop.reached(&start, &at(0, 149, 0, 1_499)));
+        assert!(stop.reached(&start, &at(0, 150, 0, 0)).is_some());
+        assert!(stop.reached(&start, &at(0, 149, 0, 1_500)).is_some());
+    }
+}
@@ -0,0 +1,273 @@
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use rand::{distributions::WeightedIndex, rngs::StdRng, Rng};
+use serde::Serialize;
+
+use crate::bank::Payment;
+
+/// Who pays whom during sustained load.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Topology {
+    /// Each wallet pays the next, the last paying the first.
+    Ring,
+    /// Each payment goes between two wallets picked at random.
+    Random,
+    /// Every other wallet pays the first, which pays them back in turn.
+    Star,
+    /// Every wallet pays every other wallet in turn.
+    Mesh,
+    /// Random payers pay payees picked with Zipf weights of the given
+    /// exponent, so a few wallets receive most payments.
+    Zipf(f64),
+    /// Each wallet pays the next `width` wallets in one multi-payee txn.
+    FanOut(usize),
+}
+
+impl Default for Topology {
+    fn default() -> Self {
+        Topology::Ring
+    }
+}
+
+impl Topology {
+    /// An endless stream of one-bone payments between `wallets`
+    /// following this topology. Random choices come from `rng`, so a
+    /// seeded one yields the same payments every time.
+    pub fn payments(&self, wallets: Vec<PathBuf>, rng: StdRng) -> Payments {
+        let zipf = match self {
+            Topology::Zipf(exponent) if !wallets.is_empty() => {
+                let weights = (1..=wallets.len()).map(|rank| 1.0 / (rank as f64).powf(*exponent));
+                Some(WeightedIndex::new(weights).expect("zipf weights are positive"))
+            }
+            _ => None,
+        };
+        Payments {
+            topology: self.clone(),
+            wallets,
+            rng,
+            zipf,
+            next: 0,
+        }
+    }
+}
+
+/// Parses `ring`, `random`, `star`, `mesh`, `zipf:EXPONENT` (1.0 when
+/// left out) or `fanout:WIDTH`.
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        match (parts.next().unwrap_or_default(), parts.next()) {
+            ("ring", None) => Ok(Topology::Ring),
+            ("random", None) => Ok(Topology::Random),
+            ("star", None) => Ok(Topology::Star),
+            ("mesh", None) => Ok(Topology::Mesh),
+            ("zipf", exponent) => match exponent.unwrap_or("1").parse::<f64>() {
+                Ok(exponent) if exponent > 0.0 => Ok(Topology::Zipf(exponent)),
+                _ => Err(format!("invalid zipf exponent in {}", s)),
+            },
+            ("fanout", Some(width)) => match width.parse() {
+                Ok(width) if width > 0 => Ok(Topology::FanOut(width)),
+                _ => Err(format!("invalid fanout width in {}", s)),
+            },
+            _ => Err(format!(
+                "invalid topology {}, expected ring, random, star, mesh, zipf:EXPONENT or fanout:WIDTH",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Topology::Ring => write!(f, "ring"),
+            Topology::Random => write!(f, "random"),
+            Topology::Star => write!(f, "star"),
+            Topology::Mesh => write!(f, "mesh"),
+            Topology::Zipf(exponent) => write!(f, "zipf:{}", exponent),
+            Topology::FanOut(width) => write!(f, "fanout:{}", width),
+        }
+    }
+}
+
+/// The payments of a `Topology`, generated as they are needed.
+pub struct Payments {
+    topology: Topology,
+    wallets: Vec<PathBuf>,
+    rng: StdRng,
+    zipf: Option<WeightedIndex<f64>>,
+    next: usize,
+}
+
+impl Payments {
+    /// A wallet other than `payer`, picked by `pick`.
+    fn other(&mut self, payer: usize, pick: impl Fn(&mut Self) -> usize) -> usize {
+        loop {
+            let payee = pick(self);
+            if payee != payer {
+                return payee;
+            }
+        }
+    }
+
+    fn payment(&self, payer: usize, payees: &[usize]) -> Payment {
+        Payment::new_multi(
+            self.wallets[payer].clone(),
+            payees.iter().map(|i| self.wallets[*i].clone()).collect(),
+            1,
+        )
+    }
+}
+
+impl Iterator for Payments {
+    type Item = Payment;
+
+    /// Ends right away when there are fewer than two wallets, as no
+    /// wallet can pay itself.
+    fn next(&mut self) -> Option<Payment> {
+        let n = self.wallets.len();
+        if n < 2 {
+            return None;
+        }
+        let i = self.next;
+        self.next = self.next.wrapping_add(1);
+
+        let (payer, payees) = match self.topology.clone() {
+            Topology::Ring => (i % n, vec![(i + 1) % n]),
+            Topology::Random => {
+                let payer = self.rng.gen_range(0, n);
+                (payer, vec![self.other(payer, |p| p.rng.gen_range(0, n))])
+            }
+            // Spokes pay the hub on even turns, the hub pays them back
+            // on odd ones
+            Topology::Star => {
+                let spoke = 1 + (i / 2) % (n - 1);
+                if i % 2 == 0 {
+                    (spoke, vec![0])
+                } else {
+                    (0, vec![spoke])
+                }
+            }
+            Topology::Mesh => {
+                let pair = i % (n * (n - 1));
+                let payer = pair / (n - 1);
+                (payer, vec![(payer + 1 + pair % (n - 1)) % n])
+            }
+            Topology::Zipf(_) => {
+                let payer = self.rng.gen_range(0, n);
+                let payee = self.other(payer, |p| {
+                    let zipf = p.zipf.as_ref().expect("zipf weights");
+                    p.rng.sample(zipf)
+                });
+                (payer, vec![payee])
+            }
+            Topology::FanOut(width) => {
+                let payer = i % n;
+                let width = width.min(n - 1);
+                (payer, (1..=width).map(|k| (payer + k) % n).collect())
+            }
+        };
+        Some(self.payment(payer, &payees))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn wallets(n: usize) -> Vec<PathBuf> {
+        (0..n).map(|i| PathBuf::from(format!("{}", i))).collect()
+    }
+
+    /// The payer and payees of the next `count` payments, as indexes.
+    fn take(topology: &str, n: usize, count: usize) -> Vec<(usize, Vec<usize>)> {
+        let index = |p: &PathBuf| p.to_string_lossy().parse::<usize>().unwrap();
+        topology
+            .parse::<Topology>()
+            .unwrap()
+            .payments(wallets(n), StdRng::from_entropy())
+            .take(count)
+            .map(|p| {
+                let payees = p.payees_key_files.iter().map(index).collect();
+                (index(&p.payer_key_file), payees)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Ok(Topology::Zipf(1.0)), "zipf".parse());
+        assert_eq!(Ok(Topology::Zipf(1.5)), "zipf:1.5".parse());
+        assert_eq!(Ok(Topology::FanOut(10)), "fanout:10".parse());
+        assert!("fanout".parse::<Topology>().is_err());
+        assert!("fanout:0".parse::<Topology>().is_err());
+        assert!("ring:2".parse::<Topology>().is_err());
+        assert!("tree".parse::<Topology>().is_err());
+        for s in &["ring", "random", "star", "mesh", "zipf:2", "fanout:3"] {
+            assert_eq!(*s, s.parse::<Topology>().unwrap().to_string());
+        }
+    }
+
+    #[test]
+    fn test_deterministic_topologies() {
+        assert_eq!(
+            vec![(0, vec![1]), (1, vec![2]), (2, vec![0]), (0, vec![1])],
+            take("ring", 3, 4)
+        );
+        assert_eq!(
+            vec![
+                (1, vec![0]),
+                (0, vec![1]),
+                (2, vec![0]),
+                (0, vec![2]),
+                (1, vec![0])
+            ],
+            take("star", 3, 5)
+        );
+        assert_eq!(
+            vec![
+                (0, vec![1]),
+                (0, vec![2]),
+                (1, vec![2]),
+                (1, vec![0]),
+                (2, vec![0]),
+                (2, vec![1]),
+            ],
+            take("mesh", 3, 6)
+        );
+        assert_eq!(
+            vec![(0, vec![1, 2]), (1, vec![2, 0]), (2, vec![0, 1])],
+            take("fanout:5", 3, 3)
+        );
+        assert!(take("ring", 1, 1).is_empty());
+    }
+
+    #[test]
+    fn test_random_topologies_never_self_pay() {
+        for topology in &["random", "zipf:1.2"] {
+            for (payer, payees) in take(topology, 5, 500) {
+                assert!(!payees.contains(&payer));
+            }
+        }
+
+        // The top ranked wallet receives far more than the last one
+        let payments = take("zipf:2", 10, 2_000);
+        let received = |i| payments.iter().filter(|(_, p)| p[0] == i).count();
+        assert!(received(0) > 10 * received(9));
+    }
+
+    #[test]
+    fn test_seeded_payments_repeat() {
+        let run = || {
+            Topology::Zipf(1.0)
+                .payments(wallets(20), StdRng::seed_from_u64(42))
+                .take(100)
+                .map(|p| (p.payer_key_file, p.payees_key_files))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(run(), run());
+    }
+}
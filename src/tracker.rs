@@ -0,0 +1,158 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::backend::TxnStatus;
+
+/// Blocks a txn may go unseen by the chain before it is considered
+/// dropped.
+const DROPPED_AFTER_BLOCKS: u64 = 10;
+
+/// A submitted txn and what is known about it so far.
+#[derive(Clone, Debug)]
+pub struct TrackedTxn {
+    pub hash: String,
+    pub payer: String,
+    pub submitted_at: Instant,
+    pub submitted_height: u64,
+    pub status: TxnStatus,
+    pub resolved_at: Option<Instant>,
+}
+
+impl TrackedTxn {
+    pub fn is_resolved(&self) -> bool {
+        match self.status {
+            TxnStatus::Cleared(_) | TxnStatus::Failed(_) => true,
+            TxnStatus::Pending | TxnStatus::Unknown => false,
+        }
+    }
+
+    /// Time from submission until the txn cleared or failed.
+    pub fn time_to_resolve(&self) -> Option<Duration> {
+        self.resolved_at.map(|at| at - self.submitted_at)
+    }
+}
+
+/// Records every submitted txn by hash and its latest status.
+#[derive(Default)]
+pub struct TxnTracker {
+    txns: Mutex<HashMap<String, TrackedTxn>>,
+}
+
+impl TxnTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, hash: &str, payer: &str, height: u64) {
+        let txn = TrackedTxn {
+            hash: hash.to_string(),
+            payer: payer.to_string(),
+            submitted_at: Instant::now(),
+            submitted_height: height,
+            status: TxnStatus::Pending,
+            resolved_at: None,
+        };
+        self.txns.lock().unwrap().insert(hash.to_string(), txn);
+    }
+
+    pub fn get(&self, hash: &str) -> Option<TrackedTxn> {
+        self.txns.lock().unwrap().get(hash).cloned()
+    }
+
+    pub fn status(&self, hash: &str) -> Option<TxnStatus> {
+        self.get(hash).map(|t| t.status)
+    }
+
+    /// True once the txn has cleared or failed. Untracked hashes
+    /// have nothing to wait for and count as resolved.
+    pub fn is_resolved(&self, hash: &str) -> bool {
+        self.get(hash).map_or(true, |t| t.is_resolved())
+    }
+
+    /// Hashes of every txn still waiting on the chain.
+    pub fn pending(&self) -> Vec<String> {
+        self.txns
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|t| !t.is_resolved())
+            .map(|t| t.hash.clone())
+            .collect()
+    }
+
+    /// Counts of (pending, cleared, failed) txns.
+    pub fn counts(&self) -> (usize, usize, usize) {
+        let txns = self.txns.lock().unwrap();
+        txns.values()
+            .fold((0, 0, 0), |(p, c, f), t| match t.status {
+                TxnStatus::Cleared(_) => (p, c + 1, f),
+                TxnStatus::Failed(_) => (p, c, f + 1),
+                TxnStatus::Pending | TxnStatus::Unknown => (p + 1, c, f),
+            })
+    }
+
+    pub fn all(&self) -> Vec<TrackedTxn> {
+        self.txns.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Applies a status fetched from the chain at `height`, returning
+    /// the txn if this update resolved it.
+    pub fn update(&self, hash: &str, status: TxnStatus, height: u64) -> Option<TrackedTxn> {
+        let mut txns = self.txns.lock().unwrap();
+        let txn = txns.get_mut(hash)?;
+        if txn.is_resolved() {
+            return None;
+        }
+
+        txn.status = match status {
+            TxnStatus::Unknown if height > txn.submitted_height + DROPPED_AFTER_BLOCKS => {
+                TxnStatus::Failed("dropped".to_string())
+            }
+            status => status,
+        };
+        if txn.is_resolved() {
+            txn.resolved_at = Some(Instant::now());
+            Some(txn.clone())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_resolves_once() {
+        let tracker = TxnTracker::new();
+        tracker.record("a", "payer", 10);
+        assert!(!tracker.is_resolved("a"));
+
+        assert!(tracker.update("a", TxnStatus::Pending, 11).is_none());
+        let txn = tracker
+            .update("a", TxnStatus::Cleared(Some(12)), 12)
+            .unwrap();
+        assert_eq!("payer", txn.payer);
+        assert!(txn.time_to_resolve().is_some());
+        assert!(tracker.update("a", TxnStatus::Pending, 13).is_none());
+        assert_eq!(Some(TxnStatus::Cleared(Some(12))), tracker.status("a"));
+    }
+
+    #[test]
+    fn test_unknown_txn_is_dropped() {
+        let tracker = TxnTracker::new();
+        tracker.record("a", "payer", 10);
+
+        assert!(tracker.update("a", TxnStatus::Unknown, 12).is_none());
+        assert!(tracker.update("a", TxnStatus::Unknown, 21).is_some());
+        assert_eq!(
+            Some(TxnStatus::Failed("dropped".to_string())),
+            tracker.status("a")
+        );
+        assert!(tracker.pending().is_empty());
+    }
+}
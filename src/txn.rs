@@ -0,0 +1,140 @@
+use helium_proto::{blockchain_txn::Txn, BlockchainTxn, BlockchainTxnPaymentV2, Payment};
+use helium_wallet::keypair::Keypair;
+use prost::Message;
+use sha2::{Digest, Sha256};
+
+use crate::error::{BankError, Result};
+
+/// A payment_v2 transaction expressed in addresses and bones, so
+/// backends can inspect it without decoding protobufs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PaymentTxn {
+    pub payer: String,
+    pub payees: Vec<(String, u64)>,
+    pub nonce: u64,
+    pub fee: u64,
+    pub signature: Vec<u8>,
+}
+
+impl PaymentTxn {
+    pub fn new(payer: &str, payees: Vec<(String, u64)>, nonce: u64) -> Self {
+        Self {
+            payer: payer.to_string(),
+            payees,
+            nonce,
+            fee: 0,
+            signature: vec![],
+        }
+    }
+
+    pub fn from_proto(txn: &BlockchainTxnPaymentV2) -> Self {
+        Self {
+            payer: bin_to_address(&txn.payer),
+            payees: txn
+                .payments
+                .iter()
+                .map(|p| (bin_to_address(&p.payee), p.amount))
+                .collect(),
+            nonce: txn.nonce,
+            fee: txn.fee,
+            signature: txn.signature.clone(),
+        }
+    }
+
+    /// Total bones moved to payees, excluding the fee.
+    pub fn amount(&self) -> u64 {
+        self.payees.iter().map(|(_, bones)| bones).sum()
+    }
+
+    pub fn to_proto(&self) -> Result<BlockchainTxnPaymentV2> {
+        let mut payments = Vec::with_capacity(self.payees.len());
+        for (address, bones) in &self.payees {
+            payments.push(Payment {
+                payee: address_to_bin(address)?,
+                amount: *bones,
+            });
+        }
+
+        Ok(BlockchainTxnPaymentV2 {
+            payer: address_to_bin(&self.payer)?,
+            payments,
+            fee: self.fee,
+            nonce: self.nonce,
+            signature: self.signature.clone(),
+        })
+    }
+
+    pub fn in_envelope(&self) -> Result<BlockchainTxn> {
+        Ok(BlockchainTxn {
+            txn: Some(Txn::PaymentV2(self.to_proto()?)),
+        })
+    }
+
+    /// Signs the txn over its encoding with an empty signature.
+    pub fn sign(&mut self, keypair: &Keypair) -> Result<()> {
+        self.signature = keypair.sign(&self.unsigned_bytes()?);
+        Ok(())
+    }
+
+    /// The txn hash as the chain reports it: sha256 of the unsigned
+    /// encoding, base64 url-safe without padding.
+    pub fn hash(&self) -> Result<String> {
+        let digest = Sha256::digest(&self.unsigned_bytes()?);
+        Ok(base64::encode_config(&digest, base64::URL_SAFE_NO_PAD))
+    }
+
+    fn unsigned_bytes(&self) -> Result<Vec<u8>> {
+        let mut txn = self.to_proto()?;
+        txn.signature = vec![];
+        let mut buf = Vec::with_capacity(txn.encoded_len());
+        // Encoding only fails for a buffer that cannot grow
+        txn.encode(&mut buf).expect("encode into a Vec");
+        Ok(buf)
+    }
+}
+
+/// Converts a b58 address into the binary public key used in txns.
+pub fn address_to_bin(address: &str) -> Result<Vec<u8>> {
+    let mut data = bs58::decode(address)
+        .with_check(Some(0))
+        .into_vec()
+        .map_err(|_| BankError::InvalidAddress(address.to_string()))?;
+    // Drop the version byte, leaving key type + key bytes
+    data.remove(0);
+    Ok(data)
+}
+
+/// Converts a binary public key back into its b58 address.
+pub fn bin_to_address(bin: &[u8]) -> String {
+    bs58::encode(bin).with_check_version(0).into_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDR: &str = "13Ad3bq7UDGYUG7xkKGAQX3vJkWQ3B5ERR3FGhhvqnEktnRNtw2";
+
+    #[test]
+    fn test_address_round_trip() {
+        let bin = address_to_bin(ADDR).unwrap();
+        assert_eq!(33, bin.len());
+        assert_eq!(ADDR, bin_to_address(&bin));
+    }
+
+    #[test]
+    fn test_proto_round_trip() {
+        let mut txn = PaymentTxn::new(ADDR, vec![(ADDR.to_string(), 10)], 7);
+        txn.fee = 3;
+        txn.signature = vec![1, 2, 3];
+        assert_eq!(txn, PaymentTxn::from_proto(&txn.to_proto().unwrap()));
+    }
+
+    #[test]
+    fn test_hash_ignores_signature() {
+        let mut txn = PaymentTxn::new(ADDR, vec![(ADDR.to_string(), 10)], 1);
+        let unsigned = txn.hash().unwrap();
+        txn.signature = vec![1, 2, 3];
+        assert_eq!(unsigned, txn.hash().unwrap());
+    }
+}
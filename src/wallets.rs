@@ -0,0 +1,268 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use helium_wallet::{keypair::Keypair, traits::ReadWrite, wallet::Wallet};
+use rayon::prelude::*;
+
+use crate::error::{BankError, Result};
+use crate::manifest::Manifest;
+
+/// Loads a wallet from file path
+pub fn load_wallet(key_file: &Path) -> Result<Wallet> {
+    let mut reader =
+        fs::File::open(key_file).map_err(|e| BankError::Io(key_file.to_path_buf(), e))?;
+    Wallet::read(&mut reader)
+        .map_err(|e| BankError::WalletDecode(key_file.to_path_buf(), e.to_string()))
+}
+
+/// A key file read from disk along with its decrypted keypair.
+pub struct KeyFile {
+    pub path: PathBuf,
+    pub address: String,
+    // The secret key is a sodiumoxide key, which zeroes its memory
+    // when dropped, so it never outlives the store holding it.
+    keypair: Option<Keypair>,
+}
+
+impl KeyFile {
+    /// Reads `path` and decrypts its keypair with `password`. An empty
+    /// password skips decryption, leaving a wallet that cannot pay.
+    pub fn load(path: &Path, password: &str) -> Result<Self> {
+        let wallet = load_wallet(path)?;
+        let address = wallet
+            .address()
+            .map_err(|e| BankError::WalletDecode(path.to_path_buf(), e.to_string()))?;
+        let keypair = if password.is_empty() {
+            None
+        } else {
+            let keypair = wallet
+                .to_keypair(password.as_bytes())
+                .map_err(|_| BankError::WrongPassword(path.to_path_buf()))?;
+            Some(keypair)
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            address,
+            keypair,
+        })
+    }
+
+    /// Reads `path` unless `manifest` already knows its address and
+    /// no keypair is needed.
+    fn load_indexed(path: &Path, password: &str, manifest: Option<&Manifest>) -> Result<Self> {
+        match manifest.and_then(|m| m.address(path)) {
+            Some(address) if password.is_empty() => Ok(Self {
+                path: path.to_path_buf(),
+                address: address.to_string(),
+                keypair: None,
+            }),
+            _ => Self::load(path, password),
+        }
+    }
+
+    pub fn keypair(&self) -> Result<&Keypair> {
+        self.keypair
+            .as_ref()
+            .ok_or_else(|| BankError::WrongPassword(self.path.clone()))
+    }
+}
+
+/// Every key file in a working directory, read and decrypted once
+/// and looked up by path or address afterwards.
+#[derive(Default)]
+pub struct WalletStore {
+    wallets: Vec<KeyFile>,
+    failed: Vec<(PathBuf, BankError)>,
+    by_path: HashMap<PathBuf, usize>,
+    by_address: HashMap<String, usize>,
+    manifest: Option<Manifest>,
+}
+
+impl WalletStore {
+    /// Loads `paths` in parallel on the current rayon pool.
+    pub fn load(paths: &[PathBuf], password: &str) -> Self {
+        let mut store = Self::default();
+        store.reload(paths, password);
+        store
+    }
+
+    /// Like `load`, but keeps the manifest in `dir` up to date and
+    /// takes addresses from it instead of reading key files when
+    /// there is no password.
+    pub fn load_indexed(dir: &Path, paths: &[PathBuf], password: &str) -> Self {
+        let mut store = Self {
+            manifest: Some(Manifest::load(dir)),
+            ..Self::default()
+        };
+        store.reload(paths, password);
+        store
+    }
+
+    /// Brings the store in line with `paths`, only reading key files
+    /// that were not loaded already.
+    pub fn reload(&mut self, paths: &[PathBuf], password: &str) {
+        let mut known: HashMap<PathBuf, KeyFile> = self
+            .wallets
+            .drain(..)
+            .map(|key| (key.path.clone(), key))
+            .collect();
+        let mut manifest = self.manifest.take();
+        let mut loaded: HashMap<PathBuf, Result<KeyFile>> = paths
+            .par_iter()
+            .filter(|p| !known.contains_key(*p))
+            .map(|p| {
+                (
+                    p.clone(),
+                    KeyFile::load_indexed(p, password, manifest.as_ref()),
+                )
+            })
+            .collect();
+
+        *self = Self::default();
+        for path in paths {
+            let r = match known.remove(path) {
+                Some(key) => Ok(key),
+                None => match loaded.remove(path) {
+                    Some(r) => r,
+                    None => continue,
+                },
+            };
+            match r {
+                Ok(key) => {
+                    self.by_path.insert(key.path.clone(), self.wallets.len());
+                    self.by_address
+                        .insert(key.address.clone(), self.wallets.len());
+                    self.wallets.push(key);
+                }
+                Err(e) => self.failed.push((path.clone(), e)),
+            }
+        }
+
+        if let Some(manifest) = &mut manifest {
+            manifest.retain(paths);
+            for key in &self.wallets {
+                manifest.insert(&key.path, &key.address);
+            }
+            // The manifest is only a cache, so a read-only working
+            // directory still works without it
+            let _ = manifest.save();
+        }
+        self.manifest = manifest;
+    }
+
+    pub fn len(&self) -> usize {
+        self.wallets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.wallets.is_empty()
+    }
+
+    /// The loaded wallets in key file order.
+    pub fn wallets(&self) -> &[KeyFile] {
+        &self.wallets
+    }
+
+    /// Key files that could not be loaded and why.
+    pub fn failed(&self) -> &[(PathBuf, BankError)] {
+        &self.failed
+    }
+
+    pub fn by_path(&self, path: &Path) -> Result<&KeyFile> {
+        self.by_path
+            .get(path)
+            .map(|i| &self.wallets[*i])
+            .ok_or_else(|| {
+                let e = io::Error::new(io::ErrorKind::NotFound, "key file is not loaded");
+                BankError::Io(path.to_path_buf(), e)
+            })
+    }
+
+    pub fn by_address(&self, address: &str) -> Result<&KeyFile> {
+        self.by_address
+            .get(address)
+            .map(|i| &self.wallets[*i])
+            .ok_or_else(|| BankError::UnknownAddress(address.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helium_wallet::cmd_create;
+
+    #[test]
+    fn test_store_lookups_and_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<PathBuf> = (1..=3)
+            .map(|i| dir.path().join(format!("wallet_{:05}.key", i)))
+            .collect();
+        for path in &paths[..2] {
+            cmd_create::cmd_basic("test-password", 2, path.clone(), false, None).unwrap();
+        }
+        fs::write(&paths[2], b"not a wallet").unwrap();
+
+        let mut store = WalletStore::load(&paths, "test-password");
+        assert_eq!(2, store.len());
+        assert_eq!(1, store.failed().len());
+        let first = store.by_path(&paths[0]).unwrap();
+        assert!(first.keypair().is_ok());
+        let address = first.address.clone();
+        assert_eq!(paths[0], store.by_address(&address).unwrap().path);
+        assert!(matches!(
+            store.by_address("nobody"),
+            Err(BankError::UnknownAddress(_))
+        ));
+
+        store.reload(&paths[1..2], "test-password");
+        assert_eq!(1, store.len());
+        assert!(store.failed().is_empty());
+        assert!(store.by_address(&address).is_err());
+    }
+
+    #[test]
+    fn test_manifest_avoids_reading_key_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet_00001.key");
+        cmd_create::cmd_basic("test-password", 2, path.clone(), false, None).unwrap();
+        let paths = vec![path.clone()];
+
+        let store = WalletStore::load_indexed(dir.path(), &paths, "test-password");
+        let address = store.wallets()[0].address.clone();
+        assert_eq!(
+            Some(address.as_str()),
+            Manifest::load(dir.path()).address(&path)
+        );
+
+        // Without a password the address comes from the manifest alone
+        let mut manifest = Manifest::load(dir.path());
+        manifest.insert(&path, "indexed");
+        manifest.save().unwrap();
+        let store = WalletStore::load_indexed(dir.path(), &paths, "");
+        assert_eq!("indexed", store.by_path(&path).unwrap().address);
+
+        // Decrypting reads the key file and corrects the manifest
+        WalletStore::load_indexed(dir.path(), &paths, "test-password");
+        assert_eq!(
+            Some(address.as_str()),
+            Manifest::load(dir.path()).address(&path)
+        );
+    }
+
+    #[test]
+    fn test_no_password_skips_decryption() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet_00001.key");
+        cmd_create::cmd_basic("test-password", 2, path.clone(), false, None).unwrap();
+
+        let key = KeyFile::load(&path, "").unwrap();
+        assert!(matches!(key.keypair(), Err(BankError::WrongPassword(_))));
+        assert!(matches!(
+            KeyFile::load(&path, "wrong-password"),
+            Err(BankError::WrongPassword(_))
+        ));
+    }
+}